@@ -1,15 +1,36 @@
+pub mod alt;
+mod edge_poll;
+pub mod group;
+pub mod interrupt;
+mod ioctl;
 pub mod mem;
 pub mod pin;
+pub mod sim;
+mod soft_pwm;
 
 use std::error;
 use std::fmt;
 use std::io;
 use std::ops::Not;
-use std::os::unix::io::AsRawFd;
 use std::result;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, Weak};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use crate::system;
+use crate::system::{DeviceInfo, SoC};
+
+use lazy_static::lazy_static;
+
+pub use self::edge_poll::EdgePoller;
+pub use self::group::{InputPinGroup, Levels, OutputPinGroup};
+pub use self::interrupt::{Edge, Event, Trigger};
+use self::interrupt::{AsyncInterrupts, Epoll};
+use self::mem::GpioMem;
+pub use self::mem::RegisterBackend;
+pub use self::pin::{AltPin, InputPin, IoPin, OutputPin, Pin};
+pub use self::sim::SimulatedGpio;
+pub use self::soft_pwm::{JitterStats, SoftPwmController};
+pub(crate) use self::soft_pwm::frequency_to_period;
 
 
 /// Errors that can occur when accessing the GPIO peripheral.
@@ -49,6 +70,30 @@ pub enum Error {
     Io(io::Error),
     /// Thread panicked.
     ThreadPanic,
+    /// Invalid alternate function mode.
+    ///
+    /// [`Pin::into_alt`] only accepts [`Alt0`] to [`Alt5`].
+    ///
+    /// [`Pin::into_alt`]: struct.Pin.html#method.into_alt
+    /// [`Alt0`]: enum.Mode.html#variant.Alt0
+    /// [`Alt5`]: enum.Mode.html#variant.Alt5
+    InvalidAltMode(Mode),
+    /// Invalid PWM frequency.
+    ///
+    /// The frequency isn't a finite value above `0.0`, or it's too low to be
+    /// converted to a period.
+    InvalidFrequency(f64),
+    /// Invalid PWM duty cycle.
+    ///
+    /// The duty cycle isn't a finite value.
+    InvalidDutyCycle(f64),
+    /// Bank is not available.
+    ///
+    /// The GPIO peripheral only has two banks. [`Gpio::read_bank`] accepts bank
+    /// `0` (pins 0-31) and bank `1` (pins 32-53).
+    ///
+    /// [`Gpio::read_bank`]: struct.Gpio.html#method.read_bank
+    BankNotAvailable(usize),
 }
 
 impl fmt::Display for Error {
@@ -59,6 +104,10 @@ impl fmt::Display for Error {
             Error::PermissionDenied(ref path) => write!(f, "Permission denied: {}", path),
             Error::Io(ref err) => write!(f, "I/O error: {}", err),
             Error::ThreadPanic => write!(f, "Thread panicked"),
+            Error::InvalidAltMode(mode) => write!(f, "{} is not an alternate function mode", mode),
+            Error::InvalidFrequency(frequency) => write!(f, "Invalid frequency: {}", frequency),
+            Error::InvalidDutyCycle(duty_cycle) => write!(f, "Invalid duty cycle: {}", duty_cycle),
+            Error::BankNotAvailable(bank) => write!(f, "Bank {} is not available", bank),
         }
     }
 }
@@ -135,4 +184,374 @@ impl Not for Level {
             Level::High => Level::Low,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Built-in pull-up/pull-down resistor states.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PullUpDown {
+    Off,
+    PullUp,
+    PullDown,
+}
+
+impl fmt::Display for PullUpDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PullUpDown::Off => write!(f, "Off"),
+            PullUpDown::PullUp => write!(f, "PullUp"),
+            PullUpDown::PullDown => write!(f, "PullDown"),
+        }
+    }
+}
+
+/// Event detect conditions, configured through the GPREN, GPFEN, GPHEN, GPLEN,
+/// GPAREN and GPAFEN registers.
+///
+/// Each condition latches the pin's bit in the GPEDS event status register until
+/// it's cleared.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EventDetect {
+    /// Synchronous rising edge, sampled using the system clock.
+    RisingEdge,
+    /// Synchronous falling edge, sampled using the system clock.
+    FallingEdge,
+    /// High level.
+    High,
+    /// Low level.
+    Low,
+    /// Asynchronous rising edge, which also catches very short pulses.
+    AsyncRisingEdge,
+    /// Asynchronous falling edge, which also catches very short pulses.
+    AsyncFallingEdge,
+}
+
+impl fmt::Display for EventDetect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EventDetect::RisingEdge => write!(f, "RisingEdge"),
+            EventDetect::FallingEdge => write!(f, "FallingEdge"),
+            EventDetect::High => write!(f, "High"),
+            EventDetect::Low => write!(f, "Low"),
+            EventDetect::AsyncRisingEdge => write!(f, "AsyncRisingEdge"),
+            EventDetect::AsyncFallingEdge => write!(f, "AsyncFallingEdge"),
+        }
+    }
+}
+
+lazy_static! {
+    // All `Gpio` instances share the same state, so the registers are only
+    // mapped once and pins can't be claimed twice through different instances.
+    static ref GPIO_STATE: Mutex<Weak<GpioState>> = Mutex::new(Weak::new());
+}
+
+// Shared between all `Gpio` instances and every `Pin` they hand out, so the
+// registers stay mapped for as long as any of them is alive.
+#[derive(Debug)]
+pub(crate) struct GpioState {
+    pub(crate) gpio_mem: GpioMem,
+    pub(crate) async_interrupts: AsyncInterrupts,
+    pins_taken: [AtomicBool; pin::MAX],
+}
+
+impl GpioState {
+    fn new(gpio_mem: GpioMem) -> GpioState {
+        GpioState {
+            gpio_mem,
+            async_interrupts: AsyncInterrupts::new(),
+            pins_taken: [(); pin::MAX].map(|_| AtomicBool::new(false)),
+        }
+    }
+
+    /// Marks `pin` as available again. Called when a [`Pin`] goes out of scope.
+    pub(crate) fn release(&self, pin: u8) {
+        self.pins_taken[pin as usize].store(false, Ordering::SeqCst);
+    }
+}
+
+// Panics unless every state is the same `GpioState`. Types that drive several
+// pins through a single `GpioMem`, such as the pin groups, check this up front.
+pub(crate) fn assert_same_gpio<'a>(mut states: impl Iterator<Item = &'a Arc<GpioState>>) {
+    if let Some(first) = states.next() {
+        assert!(
+            states.all(|state| Arc::ptr_eq(state, first)),
+            "pins belong to different Gpio instances"
+        );
+    }
+}
+
+/// Provides access to the Raspberry Pi's GPIO peripheral.
+///
+/// `Gpio` hands out [`Pin`]s through [`get`]. Each pin can only be owned by a
+/// single [`Pin`] (or a derived [`InputPin`], [`OutputPin`] or [`IoPin`]) at a time, and becomes
+/// available again once that instance goes out of scope.
+///
+/// `Gpio` is `Send` and `Sync`, and can be cloned or created multiple times.
+/// All instances share the same memory mapping and pin bookkeeping, and the
+/// pins they hand out can be moved to other threads.
+///
+/// [`get`]: #method.get
+/// [`Pin`]: struct.Pin.html
+/// [`InputPin`]: struct.InputPin.html
+/// [`OutputPin`]: struct.OutputPin.html
+/// [`IoPin`]: struct.IoPin.html
+#[derive(Debug, Clone)]
+pub struct Gpio {
+    inner: Arc<GpioState>,
+}
+
+impl Gpio {
+    /// Identifies the Raspberry Pi's SoC and maps the GPIO peripheral's registers.
+    ///
+    /// If another `Gpio` instance is still alive, its state is reused.
+    pub fn new() -> Result<Gpio> {
+        let mut static_state = GPIO_STATE.lock().unwrap();
+
+        if let Some(ref state) = static_state.upgrade() {
+            return Ok(Gpio {
+                inner: state.clone(),
+            });
+        }
+
+        let device_info = DeviceInfo::new()?;
+        let gpio_mem = GpioMem::open(&device_info)?;
+
+        let gpio_state = Arc::new(GpioState::new(gpio_mem));
+
+        *static_state = Arc::downgrade(&gpio_state);
+
+        Ok(Gpio { inner: gpio_state })
+    }
+
+    /// Constructs a `Gpio` that accesses the GPIO registers through `backend`
+    /// instead of `/dev/gpiomem`, such as a [`SimulatedGpio`].
+    ///
+    /// `soc` selects the SoC-specific register layout, for instance which
+    /// method is used to configure the pull-up/pull-down resistors. Unlike
+    /// [`new`], the returned instance doesn't share its state with other
+    /// `Gpio` instances.
+    ///
+    /// [`SimulatedGpio`]: sim/struct.SimulatedGpio.html
+    /// [`new`]: #method.new
+    pub fn with_backend<B: RegisterBackend + 'static>(backend: B, soc: SoC) -> Gpio {
+        let gpio_mem = GpioMem::with_backend(Box::new(backend), soc);
+
+        Gpio {
+            inner: Arc::new(GpioState::new(gpio_mem)),
+        }
+    }
+
+    /// Returns a [`Pin`] for the specified BCM GPIO pin number.
+    ///
+    /// Returns [`Error::PinNotAvailable`] if the pin is already in use, or if
+    /// the GPIO peripheral doesn't expose a pin with the specified number.
+    ///
+    /// [`Pin`]: struct.Pin.html
+    /// [`Error::PinNotAvailable`]: enum.Error.html#variant.PinNotAvailable
+    pub fn get(&self, pin: u8) -> Result<Pin> {
+        if pin as usize >= pin::MAX {
+            return Err(Error::PinNotAvailable(pin));
+        }
+
+        if self.inner.pins_taken[pin as usize].swap(true, Ordering::SeqCst) {
+            return Err(Error::PinNotAvailable(pin));
+        }
+
+        Ok(Pin::new(pin, self.inner.clone()))
+    }
+
+    /// Blocks until a synchronous interrupt is triggered on any of the pins, or the
+    /// timeout is reached, and returns the pin along with its logic level after
+    /// the detected edge.
+    ///
+    /// All pins are waited on through a single epoll set, so monitoring a keypad or
+    /// several limit switches doesn't need a thread per pin. Pins without a
+    /// synchronous interrupt trigger configured through [`InputPin::set_interrupt`]
+    /// are ignored.
+    ///
+    /// Setting `reset` to `false` returns any cached interrupt trigger events if
+    /// available, starting with the first pin in `pins`. Setting `reset` to `true`
+    /// clears all cached events before polling for new events.
+    ///
+    /// Setting `timeout` to `None` disables the timeout, so `poll_interrupts` blocks
+    /// until an interrupt is triggered. Returns `Ok(None)` when the timeout is reached,
+    /// or immediately if none of the pins have an interrupt trigger configured.
+    ///
+    /// [`InputPin::set_interrupt`]: struct.InputPin.html#method.set_interrupt
+    pub fn poll_interrupts<'a>(
+        &self,
+        pins: &[&'a InputPin],
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<(&'a InputPin, Level)>> {
+        let interrupts: Vec<_> = pins
+            .iter()
+            .filter_map(|&pin| pin.interrupt().map(|interrupt| (pin, interrupt)))
+            .collect();
+
+        for &(pin, interrupt) in &interrupts {
+            if reset {
+                interrupt.reset()?;
+            } else if let Some(event) = interrupt.read_event()? {
+                return Ok(Some((pin, event.level)));
+            }
+        }
+
+        if interrupts.is_empty() {
+            return Ok(None);
+        }
+
+        let epoll = Epoll::new()?;
+        for (index, &(_, interrupt)) in interrupts.iter().enumerate() {
+            epoll.add(interrupt.fd(), index as u64)?;
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+            for id in epoll.wait(remaining)? {
+                let (pin, interrupt) = interrupts[id as usize];
+                if let Some(event) = interrupt.read_event()? {
+                    return Ok(Some((pin, event.level)));
+                }
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+        }
+    }
+
+    pub(crate) fn gpio_mem(&self) -> &GpioMem {
+        &self.inner.gpio_mem
+    }
+
+    /// Reads the levels of all pins in the specified bank with a single register
+    /// read, so they're sampled at the same moment.
+    ///
+    /// Bank 0 holds pins 0-31, and bank 1 holds pins 32-53. Bit 0 of the returned
+    /// [`Levels`] corresponds to pin 0 or pin 32 respectively.
+    ///
+    /// Returns [`Error::BankNotAvailable`] if `bank` is greater than 1.
+    ///
+    /// [`Levels`]: struct.Levels.html
+    /// [`Error::BankNotAvailable`]: enum.Error.html#variant.BankNotAvailable
+    pub fn read_bank(&self, bank: usize) -> Result<Levels> {
+        if bank >= GpioMem::BANKS {
+            return Err(Error::BankNotAvailable(bank));
+        }
+
+        Ok(Levels::new(self.inner.gpio_mem.read_bank(bank)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A Gpio backed by a fresh SimulatedGpio, which is returned alongside it.
+    pub(crate) fn simulated() -> (Gpio, SimulatedGpio) {
+        let sim = SimulatedGpio::new();
+        (Gpio::with_backend(sim.clone(), SoC::Bcm2711), sim)
+    }
+
+    // Records the offsets written to, so tests can check the write order.
+    #[derive(Debug)]
+    struct Recorder {
+        sim: SimulatedGpio,
+        writes: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl RegisterBackend for Recorder {
+        fn read(&self, offset: usize) -> u32 {
+            self.sim.read(offset)
+        }
+
+        fn write(&self, offset: usize, value: u32) {
+            self.writes.lock().unwrap().push(offset);
+            self.sim.write(offset, value);
+        }
+    }
+
+    // Like `simulated`, but also returns the offsets of all register writes.
+    pub(crate) fn recorded() -> (Gpio, SimulatedGpio, Arc<Mutex<Vec<usize>>>) {
+        let sim = SimulatedGpio::new();
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            sim: sim.clone(),
+            writes: writes.clone(),
+        };
+
+        (Gpio::with_backend(recorder, SoC::Bcm2711), sim, writes)
+    }
+
+    #[test]
+    fn get_hands_out_each_pin_once() {
+        let (gpio, _sim) = simulated();
+
+        let pin = gpio.get(17).unwrap();
+        assert!(matches!(gpio.get(17), Err(Error::PinNotAvailable(17))));
+        assert!(matches!(gpio.get(pin::MAX as u8), Err(Error::PinNotAvailable(54))));
+
+        drop(pin.into_output());
+        assert!(gpio.get(17).is_ok());
+    }
+
+    #[test]
+    fn read_bank_samples_levels() {
+        let (gpio, sim) = simulated();
+
+        sim.set_level(5, Level::High).unwrap();
+        sim.set_level(36, Level::High).unwrap();
+        assert_eq!(gpio.read_bank(0).unwrap().bits(), 1 << 5);
+        assert_eq!(gpio.read_bank(1).unwrap().level(4), Level::High);
+        assert_eq!(gpio.read_bank(1).unwrap().level(5), Level::Low);
+        assert!(matches!(gpio.read_bank(2), Err(Error::BankNotAvailable(2))));
+    }
+
+    #[test]
+    fn poll_interrupts_waits_on_all_pins() {
+        use crate::gpio::interrupt::tests::{pipe, write_event};
+        use crate::gpio::interrupt::Interrupt;
+
+        let (gpio, _sim) = simulated();
+
+        let mut writers = Vec::new();
+        let mut switches = Vec::new();
+        for &pin in [5, 6, 13].iter() {
+            let (reader, writer) = pipe();
+            let mut switch = gpio.get(pin).unwrap().into_input();
+            switch.set_test_interrupt(Interrupt::from_file(pin, reader));
+            writers.push(writer);
+            switches.push(switch);
+        }
+        let unconfigured = gpio.get(19).unwrap().into_input();
+        let pins: Vec<&InputPin> = switches.iter().chain(Some(&unconfigured)).collect();
+
+        let timeout = Some(Duration::from_millis(10));
+        assert!(gpio.poll_interrupts(&pins, false, timeout).unwrap().is_none());
+
+        write_event(&mut writers[2], true);
+        let (pin, level) = gpio.poll_interrupts(&pins, false, timeout).unwrap().unwrap();
+        assert_eq!((pin.pin(), level), (13, Level::High));
+
+        // Cached events are discarded when reset is set.
+        write_event(&mut writers[0], false);
+        assert!(gpio.poll_interrupts(&pins, true, timeout).unwrap().is_none());
+
+        let writer = writers.remove(1);
+        let handle = std::thread::spawn(move || {
+            let mut writer = writer;
+            std::thread::sleep(Duration::from_millis(20));
+            write_event(&mut writer, false);
+        });
+        let (pin, level) = gpio.poll_interrupts(&pins, false, None).unwrap().unwrap();
+        assert_eq!((pin.pin(), level), (6, Level::Low));
+        handle.join().unwrap();
+
+        assert!(gpio.poll_interrupts(&[&unconfigured], false, None).unwrap().is_none());
+    }
+
+}
@@ -0,0 +1,230 @@
+use crate::gpio::Mode;
+use crate::system::SoC;
+
+/// The alternate function modes, in the order of the function tables' columns.
+pub const ALT_MODES: [Mode; 6] = [
+    Mode::Alt0,
+    Mode::Alt1,
+    Mode::Alt2,
+    Mode::Alt3,
+    Mode::Alt4,
+    Mode::Alt5,
+];
+
+// An empty string marks a reserved or unused alternate function.
+type FunctionTable = [[&'static str; 6]; 54];
+
+// BCM2835 datasheet @ 6.2. The BCM2836 and BCM2837 share the same table.
+#[rustfmt::skip]
+const BCM2835_FUNCTIONS: FunctionTable = [
+    ["SDA0", "SA5", "", "", "", ""],
+    ["SCL0", "SA4", "", "", "", ""],
+    ["SDA1", "SA3", "", "", "", ""],
+    ["SCL1", "SA2", "", "", "", ""],
+    ["GPCLK0", "SA1", "", "", "", "ARM_TDI"],
+    ["GPCLK1", "SA0", "", "", "", "ARM_TDO"],
+    ["GPCLK2", "SOE_N", "", "", "", "ARM_RTCK"],
+    ["SPI0_CE1_N", "SWE_N", "", "", "", ""],
+    ["SPI0_CE0_N", "SD0", "", "", "", ""],
+    ["SPI0_MISO", "SD1", "", "", "", ""],
+    ["SPI0_MOSI", "SD2", "", "", "", ""],
+    ["SPI0_SCLK", "SD3", "", "", "", ""],
+    ["PWM0", "SD4", "", "", "", "ARM_TMS"],
+    ["PWM1", "SD5", "", "", "", "ARM_TCK"],
+    ["TXD0", "SD6", "", "", "", "TXD1"],
+    ["RXD0", "SD7", "", "", "", "RXD1"],
+    ["", "SD8", "", "CTS0", "SPI1_CE2_N", "CTS1"],
+    ["", "SD9", "", "RTS0", "SPI1_CE1_N", "RTS1"],
+    ["PCM_CLK", "SD10", "", "BSCSL_SDA", "SPI1_CE0_N", "PWM0"],
+    ["PCM_FS", "SD11", "", "BSCSL_SCL", "SPI1_MISO", "PWM1"],
+    ["PCM_DIN", "SD12", "", "BSCSL_MISO", "SPI1_MOSI", "GPCLK0"],
+    ["PCM_DOUT", "SD13", "", "BSCSL_CE_N", "SPI1_SCLK", "GPCLK1"],
+    ["", "SD14", "", "SD1_CLK", "ARM_TRST", ""],
+    ["", "SD15", "", "SD1_CMD", "ARM_RTCK", ""],
+    ["", "SD16", "", "SD1_DAT0", "ARM_TDO", ""],
+    ["", "SD17", "", "SD1_DAT1", "ARM_TCK", ""],
+    ["", "", "", "SD1_DAT2", "ARM_TDI", ""],
+    ["", "", "", "SD1_DAT3", "ARM_TMS", ""],
+    ["SDA0", "SA5", "PCM_CLK", "", "", ""],
+    ["SCL0", "SA4", "PCM_FS", "", "", ""],
+    ["", "SA3", "PCM_DIN", "CTS0", "", "CTS1"],
+    ["", "SA2", "PCM_DOUT", "RTS0", "", "RTS1"],
+    ["GPCLK0", "SA1", "", "TXD0", "", "TXD1"],
+    ["", "SA0", "", "RXD0", "", "RXD1"],
+    ["GPCLK0", "SOE_N", "", "", "", ""],
+    ["SPI0_CE1_N", "SWE_N", "", "", "", ""],
+    ["SPI0_CE0_N", "SD0", "TXD0", "", "", ""],
+    ["SPI0_MISO", "SD1", "RXD0", "", "", ""],
+    ["SPI0_MOSI", "SD2", "RTS0", "", "", ""],
+    ["SPI0_SCLK", "SD3", "CTS0", "", "", ""],
+    ["PWM0", "SD4", "", "", "SPI2_MISO", "TXD1"],
+    ["PWM1", "SD5", "", "", "SPI2_MOSI", "RXD1"],
+    ["GPCLK1", "SD6", "", "", "SPI2_SCLK", "RTS1"],
+    ["GPCLK2", "SD7", "", "", "SPI2_CE0_N", "CTS1"],
+    ["GPCLK1", "SDA0", "SDA1", "", "SPI2_CE1_N", ""],
+    ["PWM1", "SCL0", "SCL1", "", "SPI2_CE2_N", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "SD1_CLK", "", ""],
+    ["", "", "", "SD1_CMD", "", ""],
+    ["", "", "", "SD1_DAT0", "", ""],
+    ["", "", "", "SD1_DAT1", "", ""],
+    ["", "", "", "SD1_DAT2", "", ""],
+    ["", "", "", "SD1_DAT3", "", ""],
+];
+
+// BCM2711 datasheet @ 5.3. Pins 46-57 are only used internally.
+#[rustfmt::skip]
+const BCM2711_FUNCTIONS: FunctionTable = [
+    ["SDA0", "SA5", "PCLK", "SPI3_CE0_N", "TXD2", "SDA6"],
+    ["SCL0", "SA4", "DE", "SPI3_MISO", "RXD2", "SCL6"],
+    ["SDA1", "SA3", "LCD_VSYNC", "SPI3_MOSI", "CTS2", "SDA3"],
+    ["SCL1", "SA2", "LCD_HSYNC", "SPI3_SCLK", "RTS2", "SCL3"],
+    ["GPCLK0", "SA1", "DPI_D0", "SPI4_CE0_N", "TXD3", "SDA3"],
+    ["GPCLK1", "SA0", "DPI_D1", "SPI4_MISO", "RXD3", "SCL3"],
+    ["GPCLK2", "SOE_N", "DPI_D2", "SPI4_MOSI", "CTS3", "SDA4"],
+    ["SPI0_CE1_N", "SWE_N", "DPI_D3", "SPI4_SCLK", "RTS3", "SCL4"],
+    ["SPI0_CE0_N", "SD0", "DPI_D4", "BSCSL_CE_N", "TXD4", "SDA4"],
+    ["SPI0_MISO", "SD1", "DPI_D5", "BSCSL_MISO", "RXD4", "SCL4"],
+    ["SPI0_MOSI", "SD2", "DPI_D6", "BSCSL_SDA", "CTS4", "SDA5"],
+    ["SPI0_SCLK", "SD3", "DPI_D7", "BSCSL_SCL", "RTS4", "SCL5"],
+    ["PWM0_0", "SD4", "DPI_D8", "SPI5_CE0_N", "TXD5", "SDA5"],
+    ["PWM0_1", "SD5", "DPI_D9", "SPI5_MISO", "RXD5", "SCL5"],
+    ["TXD0", "SD6", "DPI_D10", "SPI5_MOSI", "CTS5", "TXD1"],
+    ["RXD0", "SD7", "DPI_D11", "SPI5_SCLK", "RTS5", "RXD1"],
+    ["", "SD8", "DPI_D12", "CTS0", "SPI1_CE2_N", "CTS1"],
+    ["", "SD9", "DPI_D13", "RTS0", "SPI1_CE1_N", "RTS1"],
+    ["PCM_CLK", "SD10", "DPI_D14", "SPI6_CE0_N", "SPI1_CE0_N", "PWM0_0"],
+    ["PCM_FS", "SD11", "DPI_D15", "SPI6_MISO", "SPI1_MISO", "PWM0_1"],
+    ["PCM_DIN", "SD12", "DPI_D16", "SPI6_MOSI", "SPI1_MOSI", "GPCLK0"],
+    ["PCM_DOUT", "SD13", "DPI_D17", "SPI6_SCLK", "SPI1_SCLK", "GPCLK1"],
+    ["SD0_CLK", "SD14", "DPI_D18", "SD1_CLK", "ARM_TRST", "SDA6"],
+    ["SD0_CMD", "SD15", "DPI_D19", "SD1_CMD", "ARM_RTCK", "SCL6"],
+    ["SD0_DAT0", "SD16", "DPI_D20", "SD1_DAT0", "ARM_TDO", "SPI3_CE1_N"],
+    ["SD0_DAT1", "SD17", "DPI_D21", "SD1_DAT1", "ARM_TCK", "SPI4_CE1_N"],
+    ["SD0_DAT2", "", "DPI_D22", "SD1_DAT2", "ARM_TDI", "SPI5_CE1_N"],
+    ["SD0_DAT3", "", "DPI_D23", "SD1_DAT3", "ARM_TMS", "SPI6_CE1_N"],
+    ["SDA0", "SA5", "PCM_CLK", "", "MII_A_RX_ERR", "RGMII_MDIO"],
+    ["SCL0", "SA4", "PCM_FS", "", "MII_A_TX_ERR", "RGMII_MDC"],
+    ["", "SA3", "PCM_DIN", "CTS0", "MII_A_CRS", "CTS1"],
+    ["", "SA2", "PCM_DOUT", "RTS0", "MII_A_COL", "RTS1"],
+    ["GPCLK0", "SA1", "", "TXD0", "SD_CARD_PRES", "TXD1"],
+    ["", "SA0", "", "RXD0", "SD_CARD_WRPROT", "RXD1"],
+    ["GPCLK0", "SOE_N", "", "SD1_CLK", "SD_CARD_LED", "RGMII_IRQ"],
+    ["SPI0_CE1_N", "SWE_N", "", "SD1_CMD", "RGMII_START_STOP", ""],
+    ["SPI0_CE0_N", "SD0", "TXD0", "SD1_DAT0", "RGMII_RX_OK", "MII_A_RX_ERR"],
+    ["SPI0_MISO", "SD1", "RXD0", "SD1_DAT1", "RGMII_MDIO", "MII_A_TX_ERR"],
+    ["SPI0_MOSI", "SD2", "RTS0", "SD1_DAT2", "RGMII_MDC", "MII_A_CRS"],
+    ["SPI0_SCLK", "SD3", "CTS0", "SD1_DAT3", "RGMII_IRQ", "MII_A_COL"],
+    ["PWM1_0", "SD4", "", "SD1_DAT4", "SPI0_MISO", "TXD1"],
+    ["PWM1_1", "SD5", "", "SD1_DAT5", "SPI0_MOSI", "RXD1"],
+    ["GPCLK1", "SD6", "", "SD1_DAT6", "SPI0_SCLK", "RTS1"],
+    ["GPCLK2", "SD7", "", "SD1_DAT7", "SPI0_CE0_N", "CTS1"],
+    ["GPCLK1", "SDA0", "SDA1", "", "SPI0_CE1_N", "SD_CARD_VOLT"],
+    ["PWM0_1", "SCL0", "SCL1", "", "SPI0_CE2_N", "SD_CARD_PWR0"],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+    ["", "", "", "", "", ""],
+];
+
+fn table(soc: SoC) -> Option<&'static FunctionTable> {
+    match soc {
+        SoC::Bcm2835 | SoC::Bcm2836 | SoC::Bcm2837 => Some(&BCM2835_FUNCTIONS),
+        SoC::Bcm2711 => Some(&BCM2711_FUNCTIONS),
+        _ => None,
+    }
+}
+
+fn alt_index(mode: Mode) -> Option<usize> {
+    ALT_MODES.iter().position(|&alt| alt == mode)
+}
+
+/// Returns the peripheral function the pin carries when it's set to the
+/// specified alternate function mode.
+///
+/// Function names follow the SoC's datasheet, for instance `PWM0_0`, `SPI0_MOSI`,
+/// or `TXD1` for UART1's transmit line. Returns `None` for [`Input`] and
+/// [`Output`], for reserved alternate functions, and for SoCs without a table.
+///
+/// [`Input`]: ../enum.Mode.html#variant.Input
+/// [`Output`]: ../enum.Mode.html#variant.Output
+pub fn function(soc: SoC, pin: u8, mode: Mode) -> Option<&'static str> {
+    let name = table(soc)?.get(pin as usize)?[alt_index(mode)?];
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Returns every pin and alternate function mode combination that carries
+/// the specified peripheral function, in ascending pin order.
+///
+/// ```no_run
+/// use mygpio::gpio::alt;
+/// use mygpio::system::SoC;
+///
+/// // [(12, Alt0), (18, Alt5)]
+/// println!("{:?}", alt::find(SoC::Bcm2711, "PWM0_0"));
+/// ```
+pub fn find(soc: SoC, function: &str) -> Vec<(u8, Mode)> {
+    let table = match table(soc) {
+        Some(table) => table,
+        None => return Vec::new(),
+    };
+
+    let mut pins = Vec::new();
+    for (pin, functions) in table.iter().enumerate() {
+        for (index, &name) in functions.iter().enumerate() {
+            if !name.is_empty() && name == function {
+                pins.push((pin as u8, ALT_MODES[index]));
+            }
+        }
+    }
+
+    pins
+}
+
+/// Returns a readable description of a pin's mode, such as `Out`,
+/// or `Alt5 (PWM0_0)` when the alternate function is known.
+pub fn describe(soc: SoC, pin: u8, mode: Mode) -> String {
+    match function(soc, pin, mode) {
+        Some(name) => format!("{} ({})", mode, name),
+        None => format!("{}", mode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_pwm_spi_and_uart_pins() {
+        assert_eq!(
+            find(SoC::Bcm2835, "PWM0"),
+            vec![(12, Mode::Alt0), (18, Mode::Alt5), (40, Mode::Alt0)]
+        );
+        assert_eq!(find(SoC::Bcm2711, "PWM0_1"), vec![(13, Mode::Alt0), (19, Mode::Alt5), (45, Mode::Alt0)]);
+        assert_eq!(find(SoC::Bcm2837, "SPI0_MOSI"), vec![(10, Mode::Alt0), (38, Mode::Alt0)]);
+        assert_eq!(
+            find(SoC::Bcm2711, "TXD1"),
+            vec![(14, Mode::Alt5), (32, Mode::Alt5), (40, Mode::Alt5)]
+        );
+        assert!(find(SoC::Bcm2711, "").is_empty());
+        assert!(find(SoC::Bcm2712, "PWM0_0").is_empty());
+    }
+
+    #[test]
+    fn describe_pin_functions() {
+        assert_eq!(describe(SoC::Bcm2711, 18, Mode::Alt5), "Alt5 (PWM0_0)");
+        assert_eq!(describe(SoC::Bcm2835, 18, Mode::Alt3), "Alt3 (BSCSL_SDA)");
+        assert_eq!(describe(SoC::Bcm2835, 18, Mode::Alt2), "Alt2");
+        assert_eq!(describe(SoC::Bcm2835, 18, Mode::Output), "Out");
+    }
+}
@@ -0,0 +1,334 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libc::{self, CLOCK_MONOTONIC};
+
+use crate::gpio::interrupt::{Edge, Event, Trigger};
+use crate::gpio::mem::GpioMem;
+use crate::gpio::pin::InputPin;
+use crate::gpio::soft_pwm::next_deadline;
+use crate::gpio::{self, Error, GpioState, Level, Result};
+
+// Returns the current CLOCK_MONOTONIC time, the same clock used for the
+// timestamps of kernel edge events since Linux 5.7.
+fn monotonic_now() -> Result<Duration> {
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+
+    if unsafe { libc::clock_gettime(CLOCK_MONOTONIC, ts.as_mut_ptr()) } == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+
+    let ts = unsafe { ts.assume_init() };
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+// Returns `true` if the trigger fires on `edge`.
+fn triggers_on(trigger: Trigger, edge: Edge) -> bool {
+    match trigger {
+        Trigger::RisingEdge => edge == Edge::Rising,
+        Trigger::FallingEdge => edge == Edge::Falling,
+        Trigger::Both => true,
+    }
+}
+
+/// Edge event detection by sampling pin levels from a background thread.
+///
+/// An `EdgePoller` takes ownership of several [`InputPin`]s, and reads their
+/// levels at a fixed interval with one GPLEV read per bank. Any pin whose level
+/// changed since the previous sample produces an [`Event`], which is queued until
+/// it's retrieved through [`recv`], [`recv_timeout`] or [`try_recv`].
+///
+/// This is a fallback for systems where the GPIO character device isn't available,
+/// for instance inside containers or on older kernels, which rules out
+/// [`InputPin::set_interrupt`]. Pulses shorter than the interval can go unnoticed,
+/// and event timestamps are only accurate to within one interval.
+///
+/// [`InputPin`]: struct.InputPin.html
+/// [`Event`]: struct.Event.html
+/// [`recv`]: #method.recv
+/// [`recv_timeout`]: #method.recv_timeout
+/// [`try_recv`]: #method.try_recv
+/// [`InputPin::set_interrupt`]: struct.InputPin.html#method.set_interrupt
+#[derive(Debug)]
+pub struct EdgePoller {
+    pins: Vec<InputPin>,
+    interval: Duration,
+    events: Receiver<Event>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl EdgePoller {
+    /// Constructs a new `EdgePoller`, and starts its sampling thread.
+    ///
+    /// Events are produced for edges on any of the pins in `pins` that match
+    /// `trigger`. The pins' levels at the time the `EdgePoller` is constructed serve
+    /// as the initial snapshot, and don't produce any events.
+    ///
+    /// `interval` sets the time between two samples. Shorter intervals catch
+    /// shorter pulses, at the cost of a higher CPU load. With a zero interval,
+    /// the thread samples continuously.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `pins` mixes pins from unrelated [`Gpio`] instances.
+    ///
+    /// [`Gpio`]: struct.Gpio.html
+    pub fn new(pins: Vec<InputPin>, trigger: Trigger, interval: Duration) -> Result<EdgePoller> {
+        gpio::assert_same_gpio(pins.iter().map(|pin| &pin.pin.gpio_state));
+
+        let (event_sender, events) = mpsc::channel();
+        let (stop, stop_receiver) = mpsc::channel();
+
+        let thread = match pins.first() {
+            Some(first) => {
+                let gpio_state = first.pin.gpio_state.clone();
+
+                let mut masks = [0u32; GpioMem::BANKS];
+                for pin in &pins {
+                    masks[pin.pin() as usize / 32] |= 1 << (pin.pin() % 32);
+                }
+
+                let mut snapshot = [0u32; GpioMem::BANKS];
+                for bank in 0..GpioMem::BANKS {
+                    if masks[bank] != 0 {
+                        snapshot[bank] = gpio_state.gpio_mem.read_bank(bank);
+                    }
+                }
+
+                Some(
+                    thread::Builder::new()
+                        .name("edge-poller".to_owned())
+                        .spawn(move || {
+                            EdgePoller::run(
+                                &gpio_state,
+                                masks,
+                                snapshot,
+                                trigger,
+                                interval,
+                                &event_sender,
+                                &stop_receiver,
+                            )
+                        })?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(EdgePoller {
+            pins,
+            interval,
+            events,
+            stop: Some(stop),
+            thread,
+        })
+    }
+
+    fn run(
+        gpio_state: &GpioState,
+        masks: [u32; GpioMem::BANKS],
+        mut snapshot: [u32; GpioMem::BANKS],
+        trigger: Trigger,
+        interval: Duration,
+        events: &Sender<Event>,
+        stop: &Receiver<()>,
+    ) -> Result<()> {
+        let gpio_mem = &gpio_state.gpio_mem;
+
+        let mut next = Instant::now();
+        loop {
+            next = next_deadline(next, interval);
+
+            let now = Instant::now();
+            let wait = if next > now { next - now } else { Duration::from_secs(0) };
+            match stop.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return Ok(()),
+            }
+
+            for bank in 0..GpioMem::BANKS {
+                if masks[bank] == 0 {
+                    continue;
+                }
+
+                let levels = gpio_mem.read_bank(bank);
+                let timestamp = monotonic_now()?;
+                let mut changed = (levels ^ snapshot[bank]) & masks[bank];
+                snapshot[bank] = levels;
+
+                while changed != 0 {
+                    let bit = changed.trailing_zeros();
+                    changed &= changed - 1;
+
+                    let (edge, level) = if levels & (1 << bit) != 0 {
+                        (Edge::Rising, Level::High)
+                    } else {
+                        (Edge::Falling, Level::Low)
+                    };
+                    if !triggers_on(trigger, edge) {
+                        continue;
+                    }
+
+                    let event = Event {
+                        pin: (bank * 32) as u8 + bit as u8,
+                        edge,
+                        level,
+                        timestamp,
+                    };
+
+                    if events.send(event).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the pins being sampled.
+    pub fn pins(&self) -> &[InputPin] {
+        &self.pins
+    }
+
+    /// Returns the time between two samples.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Blocks until an edge event is available, and returns it.
+    ///
+    /// Returns `None` if the sampling thread has stopped and all queued events
+    /// have been retrieved. The reason the thread stopped early, if any, is
+    /// returned by [`stop`].
+    ///
+    /// [`stop`]: #method.stop
+    pub fn recv(&self) -> Option<Event> {
+        self.events.recv().ok()
+    }
+
+    /// Blocks until an edge event is available or `timeout` elapses.
+    ///
+    /// Returns `None` if the timeout elapsed, or if the sampling thread has stopped
+    /// and all queued events have been retrieved.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the oldest queued edge event without blocking, or `None` if the queue
+    /// is empty.
+    pub fn try_recv(&self) -> Option<Event> {
+        match self.events.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Stops the sampling thread.
+    ///
+    /// Events detected before the thread stopped can still be retrieved. Returns
+    /// the error that stopped the thread early, if any.
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop.take();
+
+        match self.thread.take() {
+            Some(thread) => match thread.join() {
+                Ok(result) => result,
+                Err(_) => Err(Error::ThreadPanic),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for EdgePoller {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::tests::simulated;
+
+    #[test]
+    fn monotonic_clock_moves_forward() {
+        let before = monotonic_now().unwrap();
+        thread::sleep(Duration::from_millis(1));
+        assert!(monotonic_now().unwrap() > before);
+    }
+
+    #[test]
+    fn triggers_filter_edges() {
+        assert!(triggers_on(Trigger::RisingEdge, Edge::Rising));
+        assert!(!triggers_on(Trigger::RisingEdge, Edge::Falling));
+        assert!(triggers_on(Trigger::FallingEdge, Edge::Falling));
+        assert!(!triggers_on(Trigger::FallingEdge, Edge::Rising));
+        assert!(triggers_on(Trigger::Both, Edge::Rising));
+        assert!(triggers_on(Trigger::Both, Edge::Falling));
+    }
+
+    #[test]
+    fn samples_levels_from_the_backend() {
+        let (gpio, sim) = simulated();
+
+        sim.set_level(27, Level::High).unwrap();
+        let pins = [17, 27, 33]
+            .iter()
+            .map(|&pin| gpio.get(pin).unwrap().into_input())
+            .collect();
+        let mut poller = EdgePoller::new(pins, Trigger::Both, Duration::from_millis(1)).unwrap();
+        assert_eq!(poller.pins().len(), 3);
+        assert_eq!(poller.interval(), Duration::from_millis(1));
+        let timeout = Duration::from_secs(5);
+
+        // The initial levels don't count as edges.
+        assert!(poller.recv_timeout(Duration::from_millis(10)).is_none());
+
+        let mut prev_timestamp = Duration::from_secs(0);
+        for &(pin, level) in [
+            (17, Level::High),
+            (33, Level::High),
+            (17, Level::Low),
+            (27, Level::Low),
+            (33, Level::Low),
+        ]
+        .iter()
+        {
+            sim.set_level(pin, level).unwrap();
+            let event = poller.recv_timeout(timeout).unwrap();
+            assert_eq!((event.pin, event.level), (pin, level));
+            assert_eq!(
+                event.edge,
+                if level == Level::High {
+                    Edge::Rising
+                } else {
+                    Edge::Falling
+                }
+            );
+            assert!(event.timestamp > prev_timestamp);
+            prev_timestamp = event.timestamp;
+        }
+        assert!(poller.try_recv().is_none());
+
+        poller.stop().unwrap();
+        assert!(poller.recv().is_none());
+    }
+
+    #[test]
+    fn filters_edges_by_trigger() {
+        let (gpio, sim) = simulated();
+
+        let pins = vec![gpio.get(22).unwrap().into_input()];
+        let poller = EdgePoller::new(pins, Trigger::FallingEdge, Duration::from_millis(1)).unwrap();
+
+        sim.set_level(22, Level::High).unwrap();
+        assert!(poller.recv_timeout(Duration::from_millis(20)).is_none());
+
+        sim.set_level(22, Level::Low).unwrap();
+        let event = poller.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((event.pin, event.edge), (22, Edge::Falling));
+    }
+}
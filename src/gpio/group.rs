@@ -0,0 +1,310 @@
+use crate::gpio::mem::GpioMem;
+use crate::gpio::pin::{InputPin, OutputPin};
+use crate::gpio::{self, Level};
+
+/// Snapshot of up to 32 pin levels, stored as a bitset.
+///
+/// `Levels` is returned by [`Gpio::read_bank`], where bit 0 is the first pin of
+/// the bank, and by [`InputPinGroup::read`], where bit 0 is the first pin of the group.
+///
+/// [`Gpio::read_bank`]: struct.Gpio.html#method.read_bank
+/// [`InputPinGroup::read`]: struct.InputPinGroup.html#method.read
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Levels {
+    bits: u32,
+}
+
+impl Levels {
+    pub(crate) fn new(bits: u32) -> Levels {
+        Levels { bits }
+    }
+
+    /// Returns the levels as a bitset, where a set bit means [`High`].
+    ///
+    /// [`High`]: enum.Level.html#variant.High
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns the level stored at the specified bit.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is greater than 31.
+    pub fn level(&self, bit: usize) -> Level {
+        assert!(bit < 32, "bit {} is out of range", bit);
+
+        if self.bits & (1 << bit) == 0 {
+            Level::Low
+        } else {
+            Level::High
+        }
+    }
+}
+
+impl From<Levels> for u32 {
+    fn from(levels: Levels) -> u32 {
+        levels.bits
+    }
+}
+
+/// Group of output pins that are updated simultaneously.
+///
+/// `OutputPinGroup`s are constructed from several [`OutputPin`]s using [`new`].
+/// Instead of writing one pin at a time, the group collects the new levels
+/// of all its pins, and issues a single GPSET and a single GPCLR write per bank.
+/// Pins in the same bank (0-31 or 32-53) are set together, and cleared together,
+/// which makes the group suitable for parallel buses such as the data lines
+/// of an HD44780 LCD or an 8-bit DAC.
+///
+/// [`OutputPin`]: struct.OutputPin.html
+/// [`new`]: #method.new
+#[derive(Debug)]
+pub struct OutputPinGroup {
+    pins: Vec<OutputPin>,
+}
+
+impl OutputPinGroup {
+    /// Constructs a new `OutputPinGroup`.
+    ///
+    /// The first pin in `pins` is bit 0 of the value passed to [`write_bits`],
+    /// the second pin is bit 1, and so on.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `pins` come from unrelated [`Gpio`] instances.
+    ///
+    /// [`write_bits`]: #method.write_bits
+    /// [`Gpio`]: struct.Gpio.html
+    pub fn new(pins: Vec<OutputPin>) -> OutputPinGroup {
+        gpio::assert_same_gpio(pins.iter().map(|pin| &pin.pin.gpio_state));
+
+        OutputPinGroup { pins }
+    }
+
+    /// Returns the number of pins in the group.
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Returns `true` if the group doesn't contain any pins.
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Returns the pins in the group.
+    pub fn pins(&self) -> &[OutputPin] {
+        &self.pins
+    }
+
+    /// Consumes the `OutputPinGroup`, and returns its pins.
+    pub fn into_pins(self) -> Vec<OutputPin> {
+        self.pins
+    }
+
+    /// Sets the output state of each pin to the value of its corresponding bit.
+    ///
+    /// Bit 0 of `value` is written to the first pin in the group. Bits beyond the
+    /// number of pins in the group are ignored, as are pins beyond the 32nd.
+    pub fn write_bits(&mut self, value: u32) {
+        let mut masks = [(0u32, 0u32); GpioMem::BANKS];
+
+        for (index, pin) in self.pins.iter().take(32).enumerate() {
+            add_to_masks(&mut masks, pin.pin(), value & (1 << index) != 0);
+        }
+
+        self.write_masks(&masks);
+    }
+
+    /// Sets the output state of each pin to the level at the same index in `levels`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the number of levels doesn't match the number of pins in the group.
+    pub fn set_levels(&mut self, levels: &[Level]) {
+        assert_eq!(
+            levels.len(),
+            self.pins.len(),
+            "number of levels doesn't match number of pins"
+        );
+
+        let mut masks = [(0u32, 0u32); GpioMem::BANKS];
+
+        for (pin, &level) in self.pins.iter().zip(levels) {
+            add_to_masks(&mut masks, pin.pin(), level == Level::High);
+        }
+
+        self.write_masks(&masks);
+    }
+
+    fn write_masks(&self, masks: &[(u32, u32); GpioMem::BANKS]) {
+        if let Some(first) = self.pins.first() {
+            let gpio_mem = &first.pin.gpio_state.gpio_mem;
+            for (bank, &(set, clear)) in masks.iter().enumerate() {
+                gpio_mem.write_bank(bank, set, clear);
+            }
+        }
+    }
+}
+
+/// Group of input pins that are read simultaneously.
+///
+/// `InputPinGroup`s are constructed from several [`InputPin`]s using [`new`].
+/// [`read`] samples all pins with a single GPLEV read per bank, rather than one
+/// read per pin, so pins in the same bank (0-31 or 32-53) are sampled coherently.
+///
+/// [`InputPin`]: struct.InputPin.html
+/// [`new`]: #method.new
+/// [`read`]: #method.read
+#[derive(Debug)]
+pub struct InputPinGroup {
+    pins: Vec<InputPin>,
+}
+
+impl InputPinGroup {
+    /// Constructs a new `InputPinGroup`.
+    ///
+    /// The first pin in `pins` is bit 0 of the [`Levels`] returned by [`read`],
+    /// the second pin is bit 1, and so on.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the group contains more than 32 pins, or mixes pins from
+    /// unrelated [`Gpio`] instances.
+    ///
+    /// [`Levels`]: struct.Levels.html
+    /// [`read`]: #method.read
+    /// [`Gpio`]: struct.Gpio.html
+    pub fn new(pins: Vec<InputPin>) -> InputPinGroup {
+        assert!(pins.len() <= 32, "an InputPinGroup holds at most 32 pins");
+
+        gpio::assert_same_gpio(pins.iter().map(|pin| &pin.pin.gpio_state));
+
+        InputPinGroup { pins }
+    }
+
+    /// Returns the number of pins in the group.
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Returns `true` if the group doesn't contain any pins.
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Returns the pins in the group.
+    pub fn pins(&self) -> &[InputPin] {
+        &self.pins
+    }
+
+    /// Consumes the `InputPinGroup`, and returns its pins.
+    pub fn into_pins(self) -> Vec<InputPin> {
+        self.pins
+    }
+
+    /// Reads the levels of all pins in the group.
+    ///
+    /// Each bank the group's pins belong to is read only once.
+    pub fn read(&self) -> Levels {
+        let first = match self.pins.first() {
+            Some(first) => first,
+            None => return Levels::new(0),
+        };
+
+        let gpio_mem = &first.pin.gpio_state.gpio_mem;
+        let mut banks = [None; GpioMem::BANKS];
+        let mut bits = 0;
+
+        for (index, pin) in self.pins.iter().enumerate() {
+            let pin = pin.pin();
+            let bank = banks[pin as usize / 32]
+                .get_or_insert_with(|| gpio_mem.read_bank(pin as usize / 32));
+
+            if *bank & (1 << (pin % 32)) != 0 {
+                bits |= 1 << index;
+            }
+        }
+
+        Levels::new(bits)
+    }
+}
+
+// Adds the pin to its bank's GPSET mask if `high` is true, or its GPCLR mask otherwise.
+fn add_to_masks(masks: &mut [(u32, u32); GpioMem::BANKS], pin: u8, high: bool) {
+    let (set, clear) = &mut masks[pin as usize / 32];
+    let bit = 1 << (pin % 32);
+
+    if high {
+        *set |= bit;
+    } else {
+        *clear |= bit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::mem;
+    use crate::gpio::tests::{recorded, simulated};
+
+    #[test]
+    fn output_pin_group_writes_each_bank_once() {
+        let (gpio, sim, writes) = recorded();
+
+        let data_pins = [4, 17, 27, 22, 40, 41];
+        let mut bus = OutputPinGroup::new(
+            data_pins
+                .iter()
+                .map(|&pin| gpio.get(pin).unwrap().into_output())
+                .collect(),
+        );
+        writes.lock().unwrap().clear();
+
+        bus.write_bits(0b01_0101);
+        assert_eq!(
+            *writes.lock().unwrap(),
+            vec![mem::GPSET0, mem::GPCLR0, mem::GPSET0 + 1, mem::GPCLR0 + 1]
+        );
+        let levels: Vec<Level> = data_pins.iter().map(|&pin| sim.level(pin).unwrap()).collect();
+        assert_eq!(
+            levels,
+            vec![Level::High, Level::Low, Level::High, Level::Low, Level::High, Level::Low]
+        );
+
+        writes.lock().unwrap().clear();
+        bus.set_levels(&[Level::Low; 6]);
+        assert_eq!(*writes.lock().unwrap(), vec![mem::GPCLR0, mem::GPCLR0 + 1]);
+        assert!(data_pins.iter().all(|&pin| sim.level(pin).unwrap() == Level::Low));
+    }
+
+    #[test]
+    fn input_pin_group_samples_levels() {
+        let (gpio, sim) = simulated();
+
+        sim.set_level(5, Level::High).unwrap();
+        sim.set_level(36, Level::High).unwrap();
+
+        let inputs = InputPinGroup::new(
+            [36, 6, 5]
+                .iter()
+                .map(|&pin| gpio.get(pin).unwrap().into_input())
+                .collect(),
+        );
+        let levels = inputs.read();
+        assert_eq!(u32::from(levels), 0b101);
+        assert_eq!(levels.level(1), Level::Low);
+    }
+
+    #[test]
+    #[should_panic(expected = "pins belong to different Gpio instances")]
+    fn rejects_pins_from_different_gpio_instances() {
+        let (first, _) = simulated();
+        let (second, _) = simulated();
+
+        OutputPinGroup::new(vec![
+            first.get(4).unwrap().into_output(),
+            second.get(17).unwrap().into_output(),
+        ]);
+    }
+}
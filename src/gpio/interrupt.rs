@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libc::{
+    self, c_int, c_void, epoll_event, pollfd, EFD_CLOEXEC, EFD_NONBLOCK, EPOLLIN, EPOLLPRI,
+    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL, POLLIN, POLLPRI,
+};
+
+use crate::gpio::ioctl;
+use crate::gpio::{Error, GpioState, Level, Result};
+
+// Maximum number of events returned by a single epoll_wait() call.
+const MAX_EVENTS: usize = 16;
+// epoll data of the eventfd used to stop the async interrupt thread. Pin
+// numbers are used for the line events.
+const WAKE_ID: u64 = u64::MAX;
+
+/// Interrupt trigger conditions.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Trigger {
+    RisingEdge,
+    FallingEdge,
+    Both,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Trigger::RisingEdge => write!(f, "RisingEdge"),
+            Trigger::FallingEdge => write!(f, "FallingEdge"),
+            Trigger::Both => write!(f, "Both"),
+        }
+    }
+}
+
+/// Signal edges.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Edge::Rising => write!(f, "Rising"),
+            Edge::Falling => write!(f, "Falling"),
+        }
+    }
+}
+
+/// Edge event detected on a pin.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Event {
+    /// The BCM GPIO pin number.
+    pub pin: u8,
+    /// The edge that was detected.
+    pub edge: Edge,
+    /// The pin's logic level after the edge, so [`High`] for a rising edge and
+    /// [`Low`] for a falling edge.
+    ///
+    /// [`High`]: ../enum.Level.html#variant.High
+    /// [`Low`]: ../enum.Level.html#variant.Low
+    pub level: Level,
+    /// Time at which the edge was detected, as reported by the kernel. Since
+    /// Linux 5.7 this is based on `CLOCK_MONOTONIC`, and on `CLOCK_REALTIME` before that.
+    /// Events from an [`EdgePoller`] are always based on `CLOCK_MONOTONIC`.
+    ///
+    /// [`EdgePoller`]: ../struct.EdgePoller.html
+    pub timestamp: Duration,
+}
+
+// Converts an optional timeout to the number of milliseconds poll() and
+// epoll_wait() expect, where -1 means wait indefinitely. Partial milliseconds are
+// rounded up, so a short timeout doesn't turn into a non-blocking call.
+pub(crate) fn timeout_millis(timeout: Option<Duration>) -> c_int {
+    match timeout {
+        Some(duration) => duration
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .min(c_int::MAX as u128) as c_int,
+        None => -1,
+    }
+}
+
+// Edge detection for a single pin, through a line event requested from /dev/gpiochipN.
+#[derive(Debug)]
+pub(crate) struct Interrupt {
+    pin: u8,
+    event_fd: File,
+}
+
+impl Interrupt {
+    pub(crate) fn new(pin: u8, trigger: Trigger) -> Result<Interrupt> {
+        let chip = ioctl::find_gpiochip()?;
+        let event_fd = ioctl::get_lineevent(&chip, pin, trigger)?;
+
+        Ok(Interrupt { pin, event_fd })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_file(pin: u8, event_fd: File) -> Interrupt {
+        Interrupt { pin, event_fd }
+    }
+
+    pub(crate) fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    pub(crate) fn fd(&self) -> RawFd {
+        self.event_fd.as_raw_fd()
+    }
+
+    /// Discards any events that were detected before now.
+    pub(crate) fn reset(&self) -> Result<()> {
+        while self.read_event()?.is_some() {}
+
+        Ok(())
+    }
+
+    /// Returns the oldest pending event, or `None` if there isn't any.
+    pub(crate) fn read_event(&self) -> Result<Option<Event>> {
+        ioctl::read_event(&self.event_fd, self.pin)
+    }
+
+    /// Waits until an event is detected, or `timeout` elapses.
+    pub(crate) fn poll(&self, reset: bool, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if reset {
+            self.reset()?;
+        } else if let Some(event) = self.read_event()? {
+            return Ok(Some(event));
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let mut fds = pollfd {
+                fd: self.fd(),
+                events: POLLIN | POLLPRI,
+                revents: 0,
+            };
+
+            let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let ready = unsafe { libc::poll(&mut fds, 1, timeout_millis(remaining)) };
+
+            if ready == -1 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(Error::Io(err));
+            } else if ready == 0 {
+                return Ok(None);
+            }
+
+            // A spurious wakeup leaves nothing to read, so keep waiting.
+            if let Some(event) = self.read_event()? {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+// Owns an epoll instance.
+#[derive(Debug)]
+pub(crate) struct Epoll {
+    fd: RawFd,
+}
+
+impl Epoll {
+    pub(crate) fn new() -> Result<Epoll> {
+        let fd = unsafe { libc::epoll_create1(EPOLL_CLOEXEC) };
+        if fd == -1 {
+            return Err(Error::Io(io::Error::last_os_error()));
+        }
+
+        Ok(Epoll { fd })
+    }
+
+    /// Adds `fd` to the interest list. `id` is returned as the event's data.
+    pub(crate) fn add(&self, fd: RawFd, id: u64) -> Result<()> {
+        let mut event = epoll_event {
+            events: (EPOLLIN | EPOLLPRI) as u32,
+            u64: id,
+        };
+
+        if unsafe { libc::epoll_ctl(self.fd, EPOLL_CTL_ADD, fd, &mut event) } == -1 {
+            return Err(Error::Io(io::Error::last_os_error()));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn delete(&self, fd: RawFd) -> Result<()> {
+        if unsafe { libc::epoll_ctl(self.fd, EPOLL_CTL_DEL, fd, std::ptr::null_mut()) } == -1 {
+            return Err(Error::Io(io::Error::last_os_error()));
+        }
+
+        Ok(())
+    }
+
+    /// Waits for events, and returns the ids of the ready file descriptors.
+    /// Returns an empty list when the timeout is reached or a signal arrived.
+    pub(crate) fn wait(&self, timeout: Option<Duration>) -> Result<Vec<u64>> {
+        let mut events = [epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+
+        let ready = unsafe {
+            libc::epoll_wait(
+                self.fd,
+                events.as_mut_ptr(),
+                MAX_EVENTS as c_int,
+                timeout_millis(timeout),
+            )
+        };
+
+        if ready == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+
+            return Err(Error::Io(err));
+        }
+
+        Ok(events[..ready as usize].iter().map(|event| event.u64).collect())
+    }
+}
+
+impl Drop for Epoll {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+// Non-blocking eventfd, used to wake up a thread that's waiting in epoll_wait().
+#[derive(Debug)]
+struct EventFd {
+    fd: File,
+}
+
+impl EventFd {
+    fn new() -> Result<EventFd> {
+        let fd = unsafe { libc::eventfd(0, EFD_CLOEXEC | EFD_NONBLOCK) };
+        if fd == -1 {
+            return Err(Error::Io(io::Error::last_os_error()));
+        }
+
+        Ok(EventFd {
+            fd: unsafe { File::from_raw_fd(fd) },
+        })
+    }
+
+    fn notify(&self) -> Result<()> {
+        let value: u64 = 1;
+        let written = unsafe {
+            libc::write(
+                self.fd.as_raw_fd(),
+                &value as *const u64 as *const c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+
+        if written == -1 {
+            return Err(Error::Io(io::Error::last_os_error()));
+        }
+
+        Ok(())
+    }
+}
+
+type Callback = Box<dyn FnMut(Level) + Send>;
+
+struct AsyncLine {
+    interrupt: Interrupt,
+    callback: Callback,
+}
+
+// A line registered with the worker. The file descriptor and the removed flag
+// are kept outside the mutex, so a line can be removed while its callback runs.
+struct RegisteredLine {
+    fd: RawFd,
+    removed: AtomicBool,
+    line: Mutex<AsyncLine>,
+}
+
+type AsyncLines = Mutex<HashMap<u8, Arc<RegisteredLine>>>;
+
+// The background thread, and the lines it's waiting on.
+struct Worker {
+    epoll: Arc<Epoll>,
+    wake: Arc<EventFd>,
+    lines: Arc<AsyncLines>,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl Worker {
+    fn spawn() -> Result<Worker> {
+        let epoll = Arc::new(Epoll::new()?);
+        let wake = Arc::new(EventFd::new()?);
+        epoll.add(wake.fd.as_raw_fd(), WAKE_ID)?;
+        let lines: Arc<AsyncLines> = Arc::new(Mutex::new(HashMap::new()));
+
+        let thread = {
+            let epoll = epoll.clone();
+            let lines = lines.clone();
+            thread::spawn(move || Worker::run(&epoll, &lines))
+        };
+
+        Ok(Worker {
+            epoll,
+            wake,
+            lines,
+            thread,
+        })
+    }
+
+    fn run(epoll: &Epoll, lines: &AsyncLines) -> Result<()> {
+        loop {
+            for id in epoll.wait(None)? {
+                if id == WAKE_ID {
+                    return Ok(());
+                }
+
+                // Callbacks are called without holding the lines lock, so they're
+                // free to configure interrupts on any pin, including their own.
+                let registered = lines.lock().unwrap().get(&(id as u8)).cloned();
+                if let Some(registered) = registered {
+                    let mut guard = registered.line.lock().unwrap();
+                    let line = &mut *guard;
+                    while !registered.removed.load(Ordering::SeqCst) {
+                        match line.interrupt.read_event()? {
+                            Some(event) => (line.callback)(event.level),
+                            None => break,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_current_thread(&self) -> bool {
+        self.thread.thread().id() == thread::current().id()
+    }
+
+    /// Stops the thread, and returns the reason it stopped early, if any.
+    fn stop(self) -> Result<()> {
+        // A thread that already stopped doesn't need waking.
+        let notified = self.wake.notify();
+
+        // When called from a callback, the thread stops once the callback returns.
+        if self.is_current_thread() {
+            return notified;
+        }
+
+        match self.thread.join() {
+            Ok(result) => result.and(notified),
+            Err(_) => Err(Error::ThreadPanic),
+        }
+    }
+}
+
+/// Asynchronous interrupt callbacks for all pins of a `Gpio` instance.
+///
+/// A single background thread waits on every registered line event with epoll,
+/// and calls the corresponding callback. The thread is started when the first
+/// line is registered, and stopped when the last one is removed.
+pub(crate) struct AsyncInterrupts {
+    worker: Mutex<Option<Worker>>,
+}
+
+impl fmt::Debug for AsyncInterrupts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pins: Vec<u8> = match *self.worker.lock().unwrap() {
+            Some(ref worker) => worker.lines.lock().unwrap().keys().copied().collect(),
+            None => Vec::new(),
+        };
+
+        f.debug_struct("AsyncInterrupts").field("pins", &pins).finish()
+    }
+}
+
+impl AsyncInterrupts {
+    pub(crate) fn new() -> AsyncInterrupts {
+        AsyncInterrupts {
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// Calls `callback` for every event detected by `interrupt`.
+    ///
+    /// Returns `Error::ThreadPanic` if the thread stopped because a callback panicked.
+    pub(crate) fn register<C>(&self, interrupt: Interrupt, callback: C) -> Result<()>
+    where
+        C: FnMut(Level) + Send + 'static,
+    {
+        let mut worker = self.worker.lock().unwrap();
+
+        if worker.as_ref().is_some_and(|worker| worker.thread.is_finished()) {
+            if let Some(worker) = worker.take() {
+                worker.stop()?;
+            }
+        }
+
+        if worker.is_none() {
+            *worker = Some(Worker::spawn()?);
+        }
+
+        if let Some(ref worker) = *worker {
+            let pin = interrupt.pin();
+            worker.epoll.add(interrupt.fd(), u64::from(pin))?;
+            worker.lines.lock().unwrap().insert(
+                pin,
+                Arc::new(RegisteredLine {
+                    fd: interrupt.fd(),
+                    removed: AtomicBool::new(false),
+                    line: Mutex::new(AsyncLine {
+                        interrupt,
+                        callback: Box::new(callback),
+                    }),
+                }),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Removes the pin's callback. Once this returns, the callback won't be called again.
+    ///
+    /// Returns `Error::ThreadPanic` if the thread stopped because a callback panicked.
+    pub(crate) fn unregister(&self, pin: u8) -> Result<()> {
+        let mut result = Ok(());
+
+        // The worker lock is released before waiting on a running callback, which
+        // may itself be registering or removing lines.
+        let (registered, on_worker_thread, stopped_worker) = {
+            let mut worker = self.worker.lock().unwrap();
+
+            let (registered, on_worker_thread, stop) = match *worker {
+                Some(ref worker) => {
+                    let registered = worker.lines.lock().unwrap().remove(&pin);
+                    if let Some(ref registered) = registered {
+                        registered.removed.store(true, Ordering::SeqCst);
+                        result = worker.epoll.delete(registered.fd);
+                    }
+
+                    let stop =
+                        worker.lines.lock().unwrap().is_empty() || worker.thread.is_finished();
+
+                    (registered, worker.is_current_thread(), stop)
+                }
+                None => (None, false, false),
+            };
+
+            (registered, on_worker_thread, if stop { worker.take() } else { None })
+        };
+
+        // A callback removing its own line doesn't wait for itself. Otherwise, this
+        // waits for a running callback to return. If the lock is poisoned, the
+        // callback panicked and took the thread down.
+        if let Some(registered) = registered {
+            if !on_worker_thread && registered.line.lock().is_err() {
+                result = Err(Error::ThreadPanic);
+            }
+        }
+
+        if let Some(worker) = stopped_worker {
+            result = result.and(worker.stop());
+        }
+
+        result
+    }
+}
+
+/// Asynchronous interrupt registered for an `InputPin`. Removes the callback
+/// when it goes out of scope.
+#[derive(Debug)]
+pub(crate) struct AsyncInterrupt {
+    pin: u8,
+    gpio_state: Arc<GpioState>,
+    cleared: bool,
+}
+
+impl AsyncInterrupt {
+    pub(crate) fn new<C>(
+        pin: u8,
+        trigger: Trigger,
+        gpio_state: Arc<GpioState>,
+        callback: C,
+    ) -> Result<AsyncInterrupt>
+    where
+        C: FnMut(Level) + Send + 'static,
+    {
+        let interrupt = Interrupt::new(pin, trigger)?;
+        gpio_state.async_interrupts.register(interrupt, callback)?;
+
+        Ok(AsyncInterrupt {
+            pin,
+            gpio_state,
+            cleared: false,
+        })
+    }
+
+    pub(crate) fn clear(mut self) -> Result<()> {
+        self.cleared = true;
+        self.gpio_state.async_interrupts.unregister(self.pin)
+    }
+}
+
+impl Drop for AsyncInterrupt {
+    fn drop(&mut self) {
+        if !self.cleared {
+            let _ = self.gpio_state.async_interrupts.unregister(self.pin);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::mpsc;
+
+    // A non-blocking pipe stands in for a line event file.
+    pub(crate) fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) }, 0);
+
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    // struct gpioevent_data: u64 timestamp, u32 id, padding.
+    pub(crate) fn write_event<W: Write>(writer: &mut W, rising: bool) {
+        let mut data = [0u8; 16];
+        data[8] = if rising { 0x01 } else { 0x02 };
+        writer.write_all(&data).unwrap();
+    }
+
+    #[test]
+    fn timeout_millis_rounds_up() {
+        assert_eq!(timeout_millis(None), -1);
+        assert_eq!(timeout_millis(Some(Duration::from_secs(0))), 0);
+        assert_eq!(timeout_millis(Some(Duration::from_micros(1))), 1);
+        assert_eq!(timeout_millis(Some(Duration::from_micros(999))), 1);
+        assert_eq!(timeout_millis(Some(Duration::from_millis(1))), 1);
+        assert_eq!(timeout_millis(Some(Duration::from_micros(1001))), 2);
+        assert_eq!(timeout_millis(Some(Duration::from_secs(u64::MAX))), c_int::MAX);
+    }
+
+    #[test]
+    fn callbacks_run_on_a_shared_thread_until_unregistered() {
+        let interrupts = AsyncInterrupts::new();
+        let (tx, rx) = mpsc::channel();
+
+        let (reader17, mut writer17) = pipe();
+        let (reader27, mut writer27) = pipe();
+        for (pin, reader) in [(17, reader17), (27, reader27)] {
+            let tx = tx.clone();
+            interrupts
+                .register(Interrupt::from_file(pin, reader), move |level| {
+                    tx.send((pin, level)).unwrap();
+                })
+                .unwrap();
+        }
+
+        write_event(&mut writer17, true);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok((17, Level::High)));
+        write_event(&mut writer27, false);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok((27, Level::Low)));
+
+        interrupts.unregister(17).unwrap();
+        assert!(interrupts.worker.lock().unwrap().is_some());
+        interrupts.unregister(27).unwrap();
+        assert!(interrupts.worker.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn callbacks_can_register_and_unregister_lines() {
+        let interrupts = Arc::new(AsyncInterrupts::new());
+        let (tx, rx) = mpsc::channel();
+
+        let (reader17, mut writer17) = pipe();
+        let (reader27, mut writer27) = pipe();
+        let mut reader27 = Some(reader27);
+
+        let callback_interrupts = interrupts.clone();
+        interrupts
+            .register(Interrupt::from_file(17, reader17), move |level| {
+                if level == Level::High {
+                    let tx = tx.clone();
+                    let reader = reader27.take().unwrap();
+                    callback_interrupts
+                        .register(Interrupt::from_file(27, reader), move |level| {
+                            tx.send((27, level)).unwrap();
+                        })
+                        .unwrap();
+                } else {
+                    // Removes another line, then its own line, which is the last one.
+                    callback_interrupts.unregister(27).unwrap();
+                    callback_interrupts.unregister(17).unwrap();
+                    tx.send((17, level)).unwrap();
+                }
+            })
+            .unwrap();
+
+        write_event(&mut writer17, true);
+        write_event(&mut writer27, true);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok((27, Level::High)));
+
+        // Both events are written at once. The second one is discarded along with
+        // the line.
+        let mut events = Vec::new();
+        write_event(&mut events, false);
+        write_event(&mut events, false);
+        writer17.write_all(&events).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok((17, Level::Low)));
+        assert!(interrupts.worker.lock().unwrap().is_none());
+        assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn callback_panic_surfaces_as_thread_panic() {
+        let interrupts = AsyncInterrupts::new();
+        let (reader, mut writer) = pipe();
+
+        interrupts
+            .register(Interrupt::from_file(4, reader), |_| panic!("callback failed"))
+            .unwrap();
+        write_event(&mut writer, true);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !interrupts.worker.lock().unwrap().as_ref().unwrap().thread.is_finished() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(matches!(interrupts.unregister(4), Err(Error::ThreadPanic)));
+        assert!(interrupts.worker.lock().unwrap().is_none());
+    }
+}
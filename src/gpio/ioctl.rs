@@ -0,0 +1,239 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::Path;
+use std::time::Duration;
+
+use libc::{self, c_int, c_void, O_CLOEXEC, O_NONBLOCK};
+
+use crate::gpio::interrupt::{Edge, Event, Trigger};
+use crate::gpio::{Error, Level, Result};
+
+// The GPIO character device ABI v1, as defined in linux/gpio.h.
+
+const PATH_DEV: &str = "/dev";
+const GPIOCHIP_PREFIX: &str = "gpiochip";
+// Labels of the gpiochip that drives the SoC's GPIO pins.
+const DRIVER_LABELS: [&str; 2] = ["pinctrl-bcm2835", "pinctrl-bcm2711"];
+const CONSUMER_LABEL: &str = "mygpio";
+
+// _IOR(0xB4, 0x01, struct gpiochip_info)
+const GPIO_GET_CHIPINFO_IOCTL: u32 = 0x8044_b401;
+// _IOWR(0xB4, 0x04, struct gpioevent_request)
+const GPIO_GET_LINEEVENT_IOCTL: u32 = 0xc030_b404;
+
+const GPIOHANDLE_REQUEST_INPUT: u32 = 1 << 0;
+
+const GPIOEVENT_REQUEST_RISING_EDGE: u32 = 1 << 0;
+const GPIOEVENT_REQUEST_FALLING_EDGE: u32 = 1 << 1;
+
+const GPIOEVENT_EVENT_RISING_EDGE: u32 = 0x01;
+
+// struct gpiochip_info
+#[repr(C)]
+struct ChipInfo {
+    name: [u8; 32],
+    label: [u8; 32],
+    lines: u32,
+}
+
+// struct gpioevent_request
+#[repr(C)]
+struct EventRequest {
+    lineoffset: u32,
+    handleflags: u32,
+    eventflags: u32,
+    consumer_label: [u8; 32],
+    fd: c_int,
+}
+
+// struct gpioevent_data
+#[repr(C)]
+#[derive(Debug, Default)]
+struct EventData {
+    timestamp: u64,
+    id: u32,
+}
+
+fn label(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    std::str::from_utf8(&bytes[..len]).unwrap_or("")
+}
+
+fn open_chip(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(O_CLOEXEC)
+        .open(path)
+        .map_err(|err| {
+            if err.kind() == io::ErrorKind::PermissionDenied {
+                Error::PermissionDenied(path.to_string_lossy().into_owned())
+            } else {
+                Error::Io(err)
+            }
+        })
+}
+
+fn chip_label(chip: &File) -> Result<String> {
+    let mut info = ChipInfo {
+        name: [0; 32],
+        label: [0; 32],
+        lines: 0,
+    };
+
+    if unsafe { libc::ioctl(chip.as_raw_fd(), GPIO_GET_CHIPINFO_IOCTL as _, &mut info) } == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+
+    Ok(label(&info.label).to_owned())
+}
+
+/// Opens the `/dev/gpiochipN` device that drives the SoC's GPIO pins.
+///
+/// A system can have several gpiochips, for instance for the GPIO expander on
+/// the Pi 3 B, so each one is checked for the BCM pin controller's label. Chips
+/// that can't be opened or identified are skipped. If none of the chips matches,
+/// the first of those errors is returned, since it may have been the one we're
+/// looking for.
+pub(crate) fn find_gpiochip() -> Result<File> {
+    let mut paths: Vec<_> = fs::read_dir(PATH_DEV)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(GPIOCHIP_PREFIX))
+        })
+        .collect();
+    paths.sort();
+
+    let mut first_err = None;
+    for path in paths {
+        match open_chip(&path).and_then(|chip| Ok((chip_label(&chip)?, chip))) {
+            Ok((label, chip)) if DRIVER_LABELS.contains(&label.as_str()) => return Ok(chip),
+            Ok(_) => {}
+            Err(err) => {
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+
+    Err(first_err.unwrap_or_else(|| {
+        Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No GPIO chip found",
+        ))
+    }))
+}
+
+/// Requests a line event for `pin`, and returns the non-blocking event file.
+///
+/// Requesting the line configures the pin as an input.
+pub(crate) fn get_lineevent(chip: &File, pin: u8, trigger: Trigger) -> Result<File> {
+    let mut request = EventRequest {
+        lineoffset: u32::from(pin),
+        handleflags: GPIOHANDLE_REQUEST_INPUT,
+        eventflags: match trigger {
+            Trigger::RisingEdge => GPIOEVENT_REQUEST_RISING_EDGE,
+            Trigger::FallingEdge => GPIOEVENT_REQUEST_FALLING_EDGE,
+            Trigger::Both => GPIOEVENT_REQUEST_RISING_EDGE | GPIOEVENT_REQUEST_FALLING_EDGE,
+        },
+        consumer_label: [0; 32],
+        fd: 0,
+    };
+    request.consumer_label[..CONSUMER_LABEL.len()].copy_from_slice(CONSUMER_LABEL.as_bytes());
+
+    if unsafe { libc::ioctl(chip.as_raw_fd(), GPIO_GET_LINEEVENT_IOCTL as _, &mut request) } == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+
+    let event_fd = unsafe { File::from_raw_fd(request.fd) };
+
+    // Pending events are drained by reading until EAGAIN.
+    let flags = unsafe { libc::fcntl(request.fd, libc::F_GETFL) };
+    if flags == -1 || unsafe { libc::fcntl(request.fd, libc::F_SETFL, flags | O_NONBLOCK) } == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+
+    Ok(event_fd)
+}
+
+/// Reads a single event from a line event file. Returns `None` if no event is pending.
+pub(crate) fn read_event(event_fd: &File, pin: u8) -> Result<Option<Event>> {
+    let mut data = EventData::default();
+
+    let read = unsafe {
+        libc::read(
+            event_fd.as_raw_fd(),
+            &mut data as *mut EventData as *mut c_void,
+            mem::size_of::<EventData>(),
+        )
+    };
+
+    if read == -1 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+
+        return Err(Error::Io(err));
+    } else if read as usize != mem::size_of::<EventData>() {
+        return Ok(None);
+    }
+
+    Ok(Some(event_from_data(&data, pin)))
+}
+
+fn event_from_data(data: &EventData, pin: u8) -> Event {
+    let (edge, level) = if data.id == GPIOEVENT_EVENT_RISING_EDGE {
+        (Edge::Rising, Level::High)
+    } else {
+        (Edge::Falling, Level::Low)
+    };
+
+    Event {
+        pin,
+        edge,
+        level,
+        timestamp: Duration::from_nanos(data.timestamp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The ioctl numbers encode the struct sizes in bits 16-29.
+    #[test]
+    fn struct_sizes_match_ioctl_numbers() {
+        assert_eq!(mem::size_of::<ChipInfo>() as u32, (GPIO_GET_CHIPINFO_IOCTL >> 16) & 0x3fff);
+        assert_eq!(mem::size_of::<EventRequest>() as u32, (GPIO_GET_LINEEVENT_IOCTL >> 16) & 0x3fff);
+        assert_eq!(mem::size_of::<EventData>(), 16);
+    }
+
+    #[test]
+    fn event_data_decodes_edge_and_timestamp() {
+        let rising = EventData {
+            timestamp: 1_500_000_000,
+            id: GPIOEVENT_EVENT_RISING_EDGE,
+        };
+        let event = event_from_data(&rising, 17);
+        assert_eq!(event.pin, 17);
+        assert_eq!(event.edge, Edge::Rising);
+        assert_eq!(event.level, Level::High);
+        assert_eq!(event.timestamp, Duration::from_millis(1500));
+
+        let falling = EventData { timestamp: 0, id: 0x02 };
+        let event = event_from_data(&falling, 17);
+        assert_eq!((event.edge, event.level), (Edge::Falling, Level::Low));
+    }
+
+    #[test]
+    fn labels_stop_at_nul() {
+        assert_eq!(label(b"pinctrl-bcm2711\0\0\0"), "pinctrl-bcm2711");
+        assert_eq!(label(b"no-terminator"), "no-terminator");
+    }
+}
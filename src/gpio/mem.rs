@@ -1,104 +1,319 @@
 
 use std::fmt;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::AsRawFd;
 use std::ptr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
 use libc::{self, c_void, off_t, size_t, MAP_FAILED, MAP_SHARED, O_SYNC, PROT_READ, PROT_WRITE};
 
-use crate::gpio::{Error, Result, Mode};
+use crate::gpio::alt;
+use crate::gpio::pin::MAX;
+use crate::gpio::{Error, EventDetect, Level, PullUpDown, Result, Mode};
 use crate::system::{DeviceInfo, SoC};
 
-macro_rules! my_match {
-   ($obj:expr, $($matcher:pat $(if $pred:expr)* => $result:expr),*) => {
-       match $obj {
-           $($matcher $(if $pred)* => $result),*
-       }
-   }
-}
-
-macro_rules! to_mode {
-    ($val:expr) => {{
-
-    }};
-}
-
 pub const PATH_DEV_GPIOMEM: &str = "/dev/gpiomem";
 const PATH_DEV_MEM: &str = "/dev/mem";
 // The BCM2835 has 41 32-bit registers related to the GPIO (datasheet @ 6.1).
-// The BCM2711 (RPi4) has 58 32-bit registers related to the GPIO.
-const GPIO_MEM_REGISTERS: usize = 58;
+// The BCM2711 (RPi4) extends that up to GPPUD_CNTRL_REG3 @ 0xf0, 61 registers in total.
+pub(crate) const GPIO_MEM_REGISTERS: usize = 61;
 const GPIO_MEM_SIZE: usize = GPIO_MEM_REGISTERS * std::mem::size_of::<u32>();
-const GPFSEL0: usize = 0x00;
-const GPSET0: usize = 0x1c / std::mem::size_of::<u32>();
-const GPCLR0: usize = 0x28 / std::mem::size_of::<u32>();
-const GPLEV0: usize = 0x34 / std::mem::size_of::<u32>();
-const GPPUD: usize = 0x94 / std::mem::size_of::<u32>();
-const GPPUDCLK0: usize = 0x98 / std::mem::size_of::<u32>();
+pub(crate) const GPFSEL0: usize = 0x00;
+pub(crate) const GPSET0: usize = 0x1c / std::mem::size_of::<u32>();
+pub(crate) const GPCLR0: usize = 0x28 / std::mem::size_of::<u32>();
+pub(crate) const GPLEV0: usize = 0x34 / std::mem::size_of::<u32>();
+pub(crate) const GPEDS0: usize = 0x40 / std::mem::size_of::<u32>();
+pub(crate) const GPREN0: usize = 0x4c / std::mem::size_of::<u32>();
+pub(crate) const GPFEN0: usize = 0x58 / std::mem::size_of::<u32>();
+pub(crate) const GPHEN0: usize = 0x64 / std::mem::size_of::<u32>();
+pub(crate) const GPLEN0: usize = 0x70 / std::mem::size_of::<u32>();
+pub(crate) const GPAREN0: usize = 0x7c / std::mem::size_of::<u32>();
+pub(crate) const GPAFEN0: usize = 0x88 / std::mem::size_of::<u32>();
+pub(crate) const GPPUD: usize = 0x94 / std::mem::size_of::<u32>();
+pub(crate) const GPPUDCLK0: usize = 0x98 / std::mem::size_of::<u32>();
 // Only available in BCM2711 (RPi4).
-const GPPUD_CNTRL_REG0: usize = 0xe4 / std::mem::size_of::<u32>();
+pub(crate) const GPPUD_CNTRL_REG0: usize = 0xe4 / std::mem::size_of::<u32>();
+// The BCM2835 datasheet asks for a 150 cycle wait between the steps of the
+// GPPUD/GPPUDCLK sequence. Sleeping a few microseconds covers that.
+const GPPUD_WAIT: Duration = Duration::from_micros(5);
 
 
-pub struct GpioMem {
+/// Word-level access to the GPIO peripheral's register block.
+///
+/// `GpioMem` talks to the hardware through a `RegisterBackend`. The default
+/// backend maps `/dev/gpiomem`, while [`SimulatedGpio`] models the registers
+/// in memory, so code built on [`Gpio`] can run without a Raspberry Pi.
+///
+/// Offsets are in 32-bit words from the start of the block, so GPLEV0
+/// (byte offset `0x34`) is at offset 13.
+///
+/// [`SimulatedGpio`]: ../sim/struct.SimulatedGpio.html
+/// [`Gpio`]: ../struct.Gpio.html
+pub trait RegisterBackend: fmt::Debug + Send + Sync {
+    /// Reads the register at `offset`.
+    ///
+    /// Offsets past the end of the register block read as `0`.
+    fn read(&self, offset: usize) -> u32;
+
+    /// Writes `value` to the register at `offset`.
+    ///
+    /// Writes to offsets past the end of the register block are ignored.
+    fn write(&self, offset: usize, value: u32);
+}
+
+// The GPIO registers, memory-mapped from /dev/gpiomem or /dev/mem. The mapping
+// is released when GpioMem drops its backend.
+#[derive(Debug)]
+struct MmapRegisters {
     mem_ptr: *mut u32,
+}
+
+// The mapped registers are only accessed through volatile reads and writes.
+unsafe impl Send for MmapRegisters {}
+unsafe impl Sync for MmapRegisters {}
+
+impl Drop for MmapRegisters {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.mem_ptr as *mut c_void, GPIO_MEM_SIZE as size_t);
+        }
+    }
+}
+
+impl RegisterBackend for MmapRegisters {
+    #[inline(always)]
+    fn read(&self, offset: usize) -> u32 {
+        if offset >= GPIO_MEM_REGISTERS {
+            return 0;
+        }
+
+        unsafe { ptr::read_volatile(self.mem_ptr.add(offset)) }
+    }
+
+    #[inline(always)]
+    fn write(&self, offset: usize, value: u32) {
+        if offset >= GPIO_MEM_REGISTERS {
+            return;
+        }
+
+        unsafe {
+            ptr::write_volatile(self.mem_ptr.add(offset), value);
+        }
+    }
+}
+
+// Opens `path` with read/write/sync flags, reporting a lack of permissions
+// as `Error::PermissionDenied` with the offending path.
+fn open_rw(path: &str) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(O_SYNC)
+        .open(path)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(String::from(path)),
+            _ => Error::Io(err),
+        })
+}
+
+fn is_missing_or_denied(err: &Error) -> bool {
+    match err {
+        Error::PermissionDenied(_) => true,
+        Error::Io(err) => err.kind() == io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+// Memory-maps the GPIO registers from `file`, which was opened from `path`.
+fn map_registers(file: &File, path: &str, offset: off_t) -> Result<*mut u32> {
+    let mem_ptr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            GPIO_MEM_SIZE,
+            PROT_READ | PROT_WRITE,
+            MAP_SHARED,
+            file.as_raw_fd(),
+            offset,
+        )
+    };
+
+    // errno has to be read right away, before anything else can overwrite it.
+    if mem_ptr == MAP_FAILED {
+        let err = io::Error::last_os_error();
+        return Err(match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(String::from(path)),
+            _ => Error::Io(err),
+        });
+    }
+
+    Ok(mem_ptr as *mut u32)
+}
+
+/// Returns the first of the enable registers for the event detect condition.
+fn event_detect_register(detect: EventDetect) -> usize {
+    match detect {
+        EventDetect::RisingEdge => GPREN0,
+        EventDetect::FallingEdge => GPFEN0,
+        EventDetect::High => GPHEN0,
+        EventDetect::Low => GPLEN0,
+        EventDetect::AsyncRisingEdge => GPAREN0,
+        EventDetect::AsyncFallingEdge => GPAFEN0,
+    }
+}
+
+/// Decodes a 3-bit GPFSEL function select field. Higher bits are ignored.
+pub(crate) fn mode_from_bits(bits: u32) -> Mode {
+    match bits & 0b111 {
+        0b000 => Mode::Input,
+        0b001 => Mode::Output,
+        0b100 => Mode::Alt0,
+        0b101 => Mode::Alt1,
+        0b110 => Mode::Alt2,
+        0b111 => Mode::Alt3,
+        0b011 => Mode::Alt4,
+        _ => Mode::Alt5,
+    }
+}
+
+pub struct GpioMem {
+    registers: Box<dyn RegisterBackend>,
     soc: SoC,
+    // Serializes read-modify-write sequences on the GPFSEL registers, so
+    // concurrent mode changes on pins sharing a register don't clobber each other.
+    fsel_lock: Mutex<()>,
+    // Serializes GPPUD_CNTRL_REG read-modify-writes and the GPPUD/GPPUDCLK sequence.
+    pud_lock: Mutex<()>,
+    // Serializes read-modify-writes on the event detect enable registers.
+    detect_lock: Mutex<()>,
 }
+
 impl fmt::Debug for GpioMem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("GpioMem")
-            .field("mem_ptr", &self.mem_ptr)
+            .field("registers", &self.registers)
             .field("soc", &self.soc)
             .finish()
     }
 }
 
+/// Lists every pin's current mode, along with its peripheral function when
+/// the pin is set to an alternate function mode.
 impl fmt::Display for GpioMem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("GpioMem")
-            .field("mem_ptr", &self.mem_ptr)
-            .field("soc", &self.soc)
-            .finish()
+        writeln!(f, "GPIO ({})", self.soc)?;
+        for pin in 0..MAX as u8 {
+            writeln!(f, "  BCM {:>2}: {}", pin, self.describe(pin))?;
+        }
+
+        Ok(())
     }
 }
 
 impl GpioMem {
-    pub(crate) fn open() -> Result<GpioMem> {
-        if let Ok(mem_ptr) = GpioMem::map_devgpiomem() {
-            Ok(
-                GpioMem{
-                    mem_ptr: mem_ptr,
-                    soc: SoC::Bcm2711,
+    /// Maps `/dev/gpiomem` into the process' address space.
+    ///
+    /// If `/dev/gpiomem` doesn't exist or can't be opened, this falls back to
+    /// mapping `/dev/mem` at the GPIO peripheral's physical address, which
+    /// requires root privileges.
+    ///
+    /// The BCM2712 (Pi 5) isn't supported, since its GPIO pins are driven by the
+    /// RP1 I/O controller, which has an entirely different register layout.
+    pub(crate) fn open(device_info: &DeviceInfo) -> Result<GpioMem> {
+        if device_info.soc() == SoC::Bcm2712 {
+            return Err(Error::UnknownModel);
+        }
+
+        let mem_ptr = match GpioMem::map_devgpiomem() {
+            Ok(mem_ptr) => mem_ptr,
+            Err(gpiomem_err) if is_missing_or_denied(&gpiomem_err) => {
+                let address = device_info.peripheral_base() + device_info.gpio_offset();
+                match GpioMem::map_devmem(address) {
+                    Ok(mem_ptr) => mem_ptr,
+                    // Without root privileges /dev/mem is expected to be off limits,
+                    // so the reason /dev/gpiomem couldn't be used is more helpful.
+                    Err(Error::PermissionDenied(_))
+                        if matches!(gpiomem_err, Error::PermissionDenied(_)) =>
+                    {
+                        return Err(gpiomem_err);
+                    }
+                    Err(devmem_err) => return Err(devmem_err),
                 }
-            )
-        } else {
-            Err(Error::Io(io::Error::last_os_error()))
+            }
+            Err(gpiomem_err) => return Err(gpiomem_err),
+        };
+
+        Ok(GpioMem::with_backend(Box::new(MmapRegisters { mem_ptr }), device_info.soc()))
+    }
+
+    /// Accesses the GPIO registers through `registers` instead of `/dev/gpiomem`.
+    pub(crate) fn with_backend(registers: Box<dyn RegisterBackend>, soc: SoC) -> GpioMem {
+        GpioMem {
+            registers,
+            soc,
+            fsel_lock: Mutex::new(()),
+            pud_lock: Mutex::new(()),
+            detect_lock: Mutex::new(()),
         }
+    }
 
+    pub(crate) fn soc(&self) -> SoC {
+        self.soc
     }
-    pub(crate) fn mode(&self, pin: u8) -> Mode {
 
-        let offset = pin / 10;
-        let reg_value = self.read(0);
+    /// Returns a readable description of the pin's current mode, such as
+    /// `Alt5 (PWM0_0)`.
+    pub(crate) fn describe(&self, pin: u8) -> String {
+        alt::describe(self.soc, pin, self.mode(pin))
+    }
+
+    /// Each GPFSEL register holds the 3-bit function select fields of 10 pins,
+    /// so pin 23 lives in GPFSEL2, bits 9-11.
+    pub(crate) fn mode(&self, pin: u8) -> Mode {
+        let offset = GPFSEL0 + (pin / 10) as usize;
+        let reg_value = self.read(offset);
         let shift = (pin % 10) * 3;
-        //println!(" reg_value: {:#034b} shift: {}", reg_value, shift);
-        let mode = unsafe { std::mem::transmute((reg_value >> shift) as u8 & 0b111) };
-        mode
+
+        mode_from_bits(reg_value >> shift)
     }
 
     pub(crate) fn set_mode(&self, pin: u8, mode: Mode) {
-        let offset = (pin / 10) as usize;
-        let reg_value = self.read(0);
+        let offset = GPFSEL0 + (pin / 10) as usize;
         let shift = (pin % 10) * 3;
+
+        let _lock = self.fsel_lock.lock().unwrap();
+        let reg_value = self.read(offset);
         self.write(
             offset,
             (reg_value & !(0b111 << shift)) | ((mode as u32) << shift),
         );
+    }
+
+    /// GPLEV0 holds the levels of pins 0-31, GPLEV1 those of pins 32-53.
+    ///    1098_7654_3210_9876_5432_1098_7654_3210
+    /// e.g 0000_0000_0000_0000_0000_0001_0000_0000 means only the 8th pin is high.
+    pub(crate) fn level(&self, pin: u8) -> Level {
+        let offset = GPLEV0 + pin as usize / 32;
+        let shift = pin % 32;
+        let reg_value = self.read(offset);
 
+        if (reg_value >> shift) & 0b1 == 0 {
+            Level::Low
+        } else {
+            Level::High
+        }
+    }
+
+    /// Number of 32-pin banks, each with its own GPSET, GPCLR and GPLEV register.
+    pub(crate) const BANKS: usize = 2;
+
+    /// Returns the levels of all pins in the bank with a single GPLEV read.
+    /// Bank 0 holds pins 0-31, bank 1 pins 32-53.
+    pub(crate) fn read_bank(&self, bank: usize) -> u32 {
+        self.read(GPLEV0 + bank)
     }
 
     /// this is not a register, so just setting bit has no affect on other pins
@@ -120,40 +335,267 @@ impl GpioMem {
         self.write(offset, 1 << shift);
     }
 
+    /// Sets the pins selected in `set` and clears the pins selected in `clear` for
+    /// the specified bank, using a single GPSET and a single GPCLR write. Bank 0
+    /// holds pins 0-31, bank 1 pins 32-53.
+    pub(crate) fn write_bank(&self, bank: usize, set: u32, clear: u32) {
+        if set != 0 {
+            self.write(GPSET0 + bank, set);
+        }
+
+        if clear != 0 {
+            self.write(GPCLR0 + bank, clear);
+        }
+    }
+
+    /// Enables or disables event detection for the pin. Each condition has its
+    /// own pair of enable registers, with a bit per pin.
+    pub(crate) fn set_event_detect(&self, pin: u8, detect: EventDetect, enabled: bool) {
+        let offset = event_detect_register(detect) + pin as usize / 32;
+        let bit = 1 << (pin % 32);
+
+        let _lock = self.detect_lock.lock().unwrap();
+        let reg_value = self.read(offset);
+        self.write(
+            offset,
+            if enabled {
+                reg_value | bit
+            } else {
+                reg_value & !bit
+            },
+        );
+    }
+
+    pub(crate) fn event_detect(&self, pin: u8, detect: EventDetect) -> bool {
+        let offset = event_detect_register(detect) + pin as usize / 32;
+
+        (self.read(offset) >> (pin % 32)) & 0b1 == 1
+    }
+
+    /// Returns the GPEDS event status bits of all pins in the bank.
+    pub(crate) fn event_status_bank(&self, bank: usize) -> u32 {
+        self.read(GPEDS0 + bank)
+    }
+
+    /// Clears the GPEDS event status bits selected in `mask`. Writing a 1 clears
+    /// a bit, and writing a 0 leaves it alone, so no read-modify-write is needed.
+    pub(crate) fn clear_event_status_bank(&self, bank: usize, mask: u32) {
+        self.write(GPEDS0 + bank, mask);
+    }
+
+    pub(crate) fn event_status(&self, pin: u8) -> bool {
+        (self.event_status_bank(pin as usize / 32) >> (pin % 32)) & 0b1 == 1
+    }
+
+    pub(crate) fn clear_event_status(&self, pin: u8) {
+        self.clear_event_status_bank(pin as usize / 32, 1 << (pin % 32));
+    }
+
+    /// Configures the built-in pull-up/pull-down resistors.
+    ///
+    /// The BCM2711 has a 2-bit field per pin in GPPUD_CNTRL_REG0-3. Older SoCs
+    /// need the GPPUD/GPPUDCLK clock-strobe sequence described in the BCM2835
+    /// datasheet @ 6.1.
+    pub(crate) fn set_pullupdown(&self, pin: u8, pud: PullUpDown) {
+        let _lock = self.pud_lock.lock().unwrap();
+
+        match self.soc {
+            SoC::Bcm2711 => {
+                let offset = GPPUD_CNTRL_REG0 + (pin / 16) as usize;
+                let shift = (pin % 16) * 2;
+                let pud_value: u32 = match pud {
+                    PullUpDown::Off => 0b00,
+                    PullUpDown::PullUp => 0b01,
+                    PullUpDown::PullDown => 0b10,
+                };
+
+                let reg_value = self.read(offset);
+                self.write(
+                    offset,
+                    (reg_value & !(0b11 << shift)) | (pud_value << shift),
+                );
+            }
+            _ => {
+                let pud_value: u32 = match pud {
+                    PullUpDown::Off => 0b00,
+                    PullUpDown::PullDown => 0b01,
+                    PullUpDown::PullUp => 0b10,
+                };
+                let clk_offset = GPPUDCLK0 + (pin / 32) as usize;
+                let shift = pin % 32;
+
+                self.write(GPPUD, pud_value);
+                thread::sleep(GPPUD_WAIT);
+                self.write(clk_offset, 1 << shift);
+                thread::sleep(GPPUD_WAIT);
+                self.write(GPPUD, 0);
+                self.write(clk_offset, 0);
+            }
+        }
+    }
+
     fn map_devgpiomem() -> Result<*mut u32> {
         // Open /dev/gpiomem with read/write/sync flags. This might fail if
         // /dev/gpiomem doesn't exist (< Raspbian Jessie), or /dev/gpiomem
         // doesn't have the appropriate permissions, or the current user is
         // not a member of the gpio group.
-        let gpiomem_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .custom_flags(O_SYNC)
-            .open(PATH_DEV_GPIOMEM)?;
+        let gpiomem_file = open_rw(PATH_DEV_GPIOMEM)?;
 
         // Memory-map /dev/gpiomem at offset 0
-        let gpiomem_ptr = unsafe {
-            libc::mmap(
-                ptr::null_mut(),
-                GPIO_MEM_SIZE,
-                PROT_READ | PROT_WRITE,
-                MAP_SHARED,
-                gpiomem_file.as_raw_fd(),
-                0,
-            )
-        };
+        map_registers(&gpiomem_file, PATH_DEV_GPIOMEM, 0)
+    }
+
+    fn map_devmem(address: u64) -> Result<*mut u32> {
+        // Open /dev/mem with read/write/sync flags. This requires root privileges.
+        let mem_file = open_rw(PATH_DEV_MEM)?;
 
-        Ok(gpiomem_ptr as *mut u32)
+        // Memory-map /dev/mem at the GPIO peripheral's physical address
+        map_registers(&mem_file, PATH_DEV_MEM, address as off_t)
     }
+
     #[inline(always)]
     fn read(&self, offset: usize) -> u32 {
-        unsafe { ptr::read_volatile(self.mem_ptr.add(offset)) }
+        self.registers.read(offset)
     }
 
     #[inline(always)]
     fn write(&self, offset: usize, value: u32) {
-        unsafe {
-            ptr::write_volatile(self.mem_ptr.add(offset), value);
+        self.registers.write(offset, value);
+    }
+}
+
+// A plain block of registers that stores whatever is written to it, so tests
+// can assert the exact values GpioMem writes.
+#[cfg(test)]
+#[derive(Debug)]
+struct FakeRegisters {
+    registers: Mutex<[u32; GPIO_MEM_REGISTERS]>,
+}
+
+#[cfg(test)]
+impl RegisterBackend for FakeRegisters {
+    fn read(&self, offset: usize) -> u32 {
+        self.registers.lock().unwrap().get(offset).copied().unwrap_or(0)
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        if let Some(register) = self.registers.lock().unwrap().get_mut(offset) {
+            *register = value;
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl GpioMem {
+    /// Creates a `GpioMem` backed by a zeroed register block instead of `/dev/gpiomem`.
+    pub(crate) fn fake(soc: SoC) -> GpioMem {
+        let registers = FakeRegisters {
+            registers: Mutex::new([0; GPIO_MEM_REGISTERS]),
+        };
+
+        GpioMem::with_backend(Box::new(registers), soc)
+    }
+
+    pub(crate) fn registers(&self) -> Vec<u32> {
+        (0..GPIO_MEM_REGISTERS).map(|offset| self.read(offset)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [Mode; 8] = [
+        Mode::Input,
+        Mode::Output,
+        Mode::Alt0,
+        Mode::Alt1,
+        Mode::Alt2,
+        Mode::Alt3,
+        Mode::Alt4,
+        Mode::Alt5,
+    ];
+
+    // Returns the offsets of all registers that differ between both snapshots.
+    fn changed(before: &[u32], after: &[u32]) -> Vec<usize> {
+        (0..GPIO_MEM_REGISTERS)
+            .filter(|&offset| before[offset] != after[offset])
+            .collect()
+    }
+
+    #[test]
+    fn set_mode_writes_only_the_pins_gpfsel_field() {
+        for pin in 0..MAX as u8 {
+            let gpio_mem = GpioMem::fake(SoC::Bcm2711);
+            // Fill every function select field, so clobbering is detectable.
+            for other in 0..MAX as u8 {
+                gpio_mem.set_mode(other, Mode::Alt3);
+            }
+
+            let before = gpio_mem.registers();
+            gpio_mem.set_mode(pin, Mode::Output);
+            let after = gpio_mem.registers();
+
+            let offset = GPFSEL0 + pin as usize / 10;
+            let shift = (pin % 10) * 3;
+            assert_eq!(changed(&before, &after), vec![offset], "pin {}", pin);
+            assert_eq!(
+                after[offset],
+                (before[offset] & !(0b111 << shift)) | (0b001 << shift),
+                "pin {}",
+                pin
+            );
+        }
+    }
+
+    #[test]
+    fn mode_reads_back_every_mode_for_every_pin() {
+        let gpio_mem = GpioMem::fake(SoC::Bcm2711);
+
+        for &mode in MODES.iter() {
+            for pin in 0..MAX as u8 {
+                gpio_mem.set_mode(pin, mode);
+            }
+            for pin in 0..MAX as u8 {
+                assert_eq!(gpio_mem.mode(pin), mode, "pin {}", pin);
+            }
+        }
+    }
+
+    #[test]
+    fn set_high_and_set_low_write_the_pins_bit() {
+        for pin in 0..MAX as u8 {
+            let gpio_mem = GpioMem::fake(SoC::Bcm2711);
+            let bank = pin as usize / 32;
+
+            gpio_mem.set_high(pin);
+            let registers = gpio_mem.registers();
+            assert_eq!(changed(&[0; GPIO_MEM_REGISTERS], &registers), vec![GPSET0 + bank]);
+            assert_eq!(registers[GPSET0 + bank], 1 << (pin % 32), "pin {}", pin);
+
+            let before = gpio_mem.registers();
+            gpio_mem.set_low(pin);
+            let after = gpio_mem.registers();
+            assert_eq!(changed(&before, &after), vec![GPCLR0 + bank]);
+            assert_eq!(after[GPCLR0 + bank], 1 << (pin % 32), "pin {}", pin);
+        }
+    }
+
+    #[test]
+    fn level_reads_the_pins_gplev_bit() {
+        let gpio_mem = GpioMem::fake(SoC::Bcm2711);
+
+        for pin in 0..MAX as u8 {
+            let offset = GPLEV0 + pin as usize / 32;
+            gpio_mem.write(GPLEV0, 0);
+            gpio_mem.write(GPLEV0 + 1, 0);
+            assert_eq!(gpio_mem.level(pin), Level::Low, "pin {}", pin);
+
+            gpio_mem.write(offset, 1 << (pin % 32));
+            assert_eq!(gpio_mem.level(pin), Level::High, "pin {}", pin);
+
+            gpio_mem.write(offset, !(1 << (pin % 32)));
+            assert_eq!(gpio_mem.level(pin), Level::Low, "pin {}", pin);
+        }
+    }
+}
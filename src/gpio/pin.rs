@@ -1,6 +1,10 @@
-use crate::gpio::mem::GpioMem;
-use std::rc::Rc;
-use crate::gpio::{Level, Mode};
+use crate::gpio::alt;
+use crate::gpio::interrupt::{AsyncInterrupt, Event, Interrupt, Trigger};
+use crate::gpio::soft_pwm::{self, SoftPwm};
+use crate::gpio::GpioState;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::gpio::{Error, EventDetect, Level, Mode, PullUpDown, Result};
 
 // Maximum GPIO pins on the BCM2835. The actual number of pins
 // exposed through the Pi's GPIO header depends on the model.
@@ -18,9 +22,55 @@ macro_rules! impl_pin {
     };
 }
 
+macro_rules! impl_input {
+    () => {
+        /// Reads the pin's logic level.
+        #[inline]
+        pub fn read(&self) -> Level {
+            self.pin.read()
+        }
+
+        /// Reads the pin's logic level, and returns `true` if it's set to [`Low`].
+        ///
+        /// [`Low`]: enum.Level.html#variant.Low
+        #[inline]
+        pub fn is_low(&self) -> bool {
+            self.pin.read() == Level::Low
+        }
+
+        /// Reads the pin's logic level, and returns `true` if it's set to [`High`].
+        ///
+        /// [`High`]: enum.Level.html#variant.High
+        #[inline]
+        pub fn is_high(&self) -> bool {
+            self.pin.read() == Level::High
+        }
+    };
+}
 
 macro_rules! impl_output {
     () => {
+        /// Returns `true` if the pin's output state is set to [`Low`].
+        ///
+        /// The state is read back from the pin's GPLEV bit, so it reflects the
+        /// level actually present on the pin.
+        ///
+        /// [`Low`]: enum.Level.html#variant.Low
+        #[inline]
+        pub fn is_set_low(&self) -> bool {
+            self.pin.read() == Level::Low
+        }
+
+        /// Returns `true` if the pin's output state is set to [`High`].
+        ///
+        /// The state is read back from the pin's GPLEV bit, so it reflects the
+        /// level actually present on the pin.
+        ///
+        /// [`High`]: enum.Level.html#variant.High
+        #[inline]
+        pub fn is_set_high(&self) -> bool {
+            self.pin.read() == Level::High
+        }
 
         /// Sets the pin's output state to [`Low`].
         ///
@@ -38,6 +88,21 @@ macro_rules! impl_output {
             self.pin.set_high()
         }
 
+        /// Sets the pin's output state.
+        #[inline]
+        pub fn write(&mut self, level: Level) {
+            self.pin.write(level)
+        }
+
+        /// Toggles the pin's output state between [`Low`] and [`High`].
+        ///
+        /// [`Low`]: enum.Level.html#variant.Low
+        /// [`High`]: enum.Level.html#variant.High
+        #[inline]
+        pub fn toggle(&mut self) {
+            let level = self.pin.read();
+            self.pin.write(!level);
+        }
     };
 }
 
@@ -66,19 +131,48 @@ macro_rules! impl_reset_on_drop {
 }
 
 macro_rules! impl_drop {
-    ($struct:ident) => {
+    (
+        $struct:ident
+        $(, soft_pwm: $soft_pwm:ident)?
+        $(, drop_level: $drop_level:ident)?
+        $(, event_detects: $event_detects:ident)?
+    ) => {
         impl Drop for $struct {
             /// Resets the pin's mode and disables the built-in pull-up/pull-down
             /// resistors if `reset_on_drop` is set to `true` (default).
             fn drop(&mut self) {
+                $(
+                    if let Some(mut soft_pwm) = self.$soft_pwm.take() {
+                        let _ = soft_pwm.stop();
+                    }
+                )?
+
+                $(
+                    if let Some(level) = self.$drop_level {
+                        self.pin.write(level);
+                    }
+                )?
+
+                $(
+                    if !self.$event_detects.is_empty() {
+                        for detect in self.$event_detects.drain(..) {
+                            self.pin.set_event_detect(detect, false);
+                        }
+                        self.pin.clear_event_detected();
+                    }
+                )?
+
                 if !self.reset_on_drop {
                     return;
                 }
 
                 if let Some(prev_mode) = self.prev_mode {
-                    println!("resetting mode");
                     self.pin.set_mode(prev_mode);
                 }
+
+                if self.pud_mode != PullUpDown::Off {
+                    self.pin.set_pullupdown(PullUpDown::Off);
+                }
             }
         }
     };
@@ -126,15 +220,15 @@ macro_rules! impl_eq {
 #[derive(Debug)]
 pub struct Pin {
     pub(crate) pin: u8,
-    gpio_mem: Rc<GpioMem>,
+    pub(crate) gpio_state: Arc<GpioState>,
 }
 
 
 
 impl Pin {
     #[inline]
-    pub(crate) fn new(pin: u8, gpio_mem: Rc<GpioMem>) -> Pin {
-        Pin { pin, gpio_mem}
+    pub(crate) fn new(pin: u8, gpio_state: Arc<GpioState>) -> Pin {
+        Pin { pin, gpio_state }
     }
 
     /// Returns the GPIO pin number.
@@ -148,7 +242,60 @@ impl Pin {
     /// Returns the pin's mode.
     #[inline]
     pub fn mode(&self) -> Mode {
-        self.gpio_mem.mode(self.pin)
+        self.gpio_state.gpio_mem.mode(self.pin)
+    }
+
+    /// Reads the pin's logic level.
+    #[inline]
+    pub fn read(&self) -> Level {
+        self.gpio_state.gpio_mem.level(self.pin)
+    }
+
+    /// Returns the peripheral function the pin currently carries, such as
+    /// `PWM0_0` or `SPI0_MOSI`.
+    ///
+    /// Returns `None` if the pin isn't set to an alternate function mode, or
+    /// if the function is unknown for this SoC.
+    #[inline]
+    pub fn function(&self) -> Option<&'static str> {
+        alt::function(self.gpio_state.gpio_mem.soc(), self.pin, self.mode())
+    }
+
+    /// Consumes the `Pin`, returns an [`InputPin`] and sets its mode to [`Input`].
+    ///
+    /// [`InputPin`]: struct.InputPin.html
+    /// [`Input`]: enum.Mode.html#variant.Input
+    #[inline]
+    pub fn into_input(self) -> InputPin {
+        InputPin::new(self, PullUpDown::Off)
+    }
+
+    /// Consumes the `Pin`, returns an [`InputPin`], sets its mode to [`Input`],
+    /// and enables the pin's built-in pull-up resistor.
+    ///
+    /// The pull-up resistor is disabled when the `InputPin` goes out of scope if
+    /// [`reset_on_drop`] is set to `true` (default).
+    ///
+    /// [`InputPin`]: struct.InputPin.html
+    /// [`Input`]: enum.Mode.html#variant.Input
+    /// [`reset_on_drop`]: struct.InputPin.html#method.set_reset_on_drop
+    #[inline]
+    pub fn into_input_pullup(self) -> InputPin {
+        InputPin::new(self, PullUpDown::PullUp)
+    }
+
+    /// Consumes the `Pin`, returns an [`InputPin`], sets its mode to [`Input`],
+    /// and enables the pin's built-in pull-down resistor.
+    ///
+    /// The pull-down resistor is disabled when the `InputPin` goes out of scope if
+    /// [`reset_on_drop`] is set to `true` (default).
+    ///
+    /// [`InputPin`]: struct.InputPin.html
+    /// [`Input`]: enum.Mode.html#variant.Input
+    /// [`reset_on_drop`]: struct.InputPin.html#method.set_reset_on_drop
+    #[inline]
+    pub fn into_input_pulldown(self) -> InputPin {
+        InputPin::new(self, PullUpDown::PullDown)
     }
 
     /// Consumes the `Pin`, returns an [`OutputPin`] and sets its mode to [`Output`].
@@ -160,19 +307,93 @@ impl Pin {
         OutputPin::new(self)
     }
 
+    /// Consumes the `Pin`, returns an [`OutputPin`], sets its output state to [`Low`]
+    /// and then sets its mode to [`Output`].
+    ///
+    /// The output state is set before the mode is changed, so the pin never
+    /// briefly drives [`High`] when it switches to [`Output`].
+    ///
+    /// [`OutputPin`]: struct.OutputPin.html
+    /// [`Low`]: enum.Level.html#variant.Low
+    /// [`High`]: enum.Level.html#variant.High
+    /// [`Output`]: enum.Mode.html#variant.Output
+    #[inline]
+    pub fn into_output_low(self) -> OutputPin {
+        OutputPin::with_initial_level(self, Level::Low)
+    }
+
+    /// Consumes the `Pin`, returns an [`OutputPin`], sets its output state to [`High`]
+    /// and then sets its mode to [`Output`].
+    ///
+    /// The output state is set before the mode is changed, so the pin never
+    /// briefly drives [`Low`] when it switches to [`Output`].
+    ///
+    /// [`OutputPin`]: struct.OutputPin.html
+    /// [`Low`]: enum.Level.html#variant.Low
+    /// [`High`]: enum.Level.html#variant.High
+    /// [`Output`]: enum.Mode.html#variant.Output
+    #[inline]
+    pub fn into_output_high(self) -> OutputPin {
+        OutputPin::with_initial_level(self, Level::High)
+    }
+
+    /// Consumes the `Pin`, returns an [`IoPin`] and sets its mode to the specified mode.
+    ///
+    /// [`IoPin`]: struct.IoPin.html
+    #[inline]
+    pub fn into_io(self, mode: Mode) -> IoPin {
+        IoPin::new(self, mode)
+    }
+
+    /// Consumes the `Pin`, returns an [`AltPin`] and sets its mode to the specified
+    /// alternate function mode.
+    ///
+    /// The peripheral functions available on each pin are listed in the [`alt`]
+    /// module, which can also be used to find the pin and mode for a specific function.
+    ///
+    /// Returns [`Error::InvalidAltMode`] if `mode` isn't one of [`Alt0`] to [`Alt5`].
+    /// The pin is released in that case, and can be retrieved again through
+    /// [`Gpio::get`].
+    ///
+    /// [`AltPin`]: struct.AltPin.html
+    /// [`alt`]: alt/index.html
+    /// [`Error::InvalidAltMode`]: enum.Error.html#variant.InvalidAltMode
+    /// [`Alt0`]: enum.Mode.html#variant.Alt0
+    /// [`Alt5`]: enum.Mode.html#variant.Alt5
+    /// [`Gpio::get`]: struct.Gpio.html#method.get
+    #[inline]
+    pub fn into_alt(self, mode: Mode) -> Result<AltPin> {
+        AltPin::new(self, mode)
+    }
+
     #[inline]
     pub(crate) fn set_mode(&mut self, mode: Mode) {
-        self.gpio_mem.set_mode(self.pin, mode);
+        self.gpio_state.gpio_mem.set_mode(self.pin, mode);
+    }
+
+    #[inline]
+    pub(crate) fn set_event_detect(&mut self, detect: EventDetect, enabled: bool) {
+        self.gpio_state.gpio_mem.set_event_detect(self.pin, detect, enabled);
+    }
+
+    #[inline]
+    pub(crate) fn clear_event_detected(&mut self) {
+        self.gpio_state.gpio_mem.clear_event_status(self.pin);
+    }
+
+    #[inline]
+    pub(crate) fn set_pullupdown(&mut self, pud: PullUpDown) {
+        self.gpio_state.gpio_mem.set_pullupdown(self.pin, pud);
     }
 
     #[inline]
     pub(crate) fn set_low(&mut self) {
-        self.gpio_mem.set_low(self.pin);
+        self.gpio_state.gpio_mem.set_low(self.pin);
     }
 
     #[inline]
     pub(crate) fn set_high(&mut self) {
-        self.gpio_mem.set_high(self.pin);
+        self.gpio_state.gpio_mem.set_high(self.pin);
     }
 
     #[inline]
@@ -185,8 +406,229 @@ impl Pin {
 
 }
 
+impl Drop for Pin {
+    /// Releases the pin, so it can be retrieved again through [`Gpio::get`].
+    ///
+    /// [`Gpio::get`]: struct.Gpio.html#method.get
+    fn drop(&mut self) {
+        self.gpio_state.release(self.pin);
+    }
+}
+
 impl_eq!(Pin);
 
+/// GPIO pin configured as input.
+///
+/// `InputPin`s are constructed by converting a [`Pin`] using [`Pin::into_input`].
+/// The pin's mode is automatically set to [`Input`].
+///
+/// An `InputPin` can be used to read a pin's logic level.
+///
+/// [`Pin`]: struct.Pin.html
+/// [`Input`]: enum.Mode.html#variant.Input
+/// [`Pin::into_input`]: struct.Pin.html#method.into_input
+#[derive(Debug)]
+pub struct InputPin {
+    prev_mode: Option<Mode>,
+    reset_on_drop: bool,
+    pud_mode: PullUpDown,
+    interrupt: Option<Interrupt>,
+    async_interrupt: Option<AsyncInterrupt>,
+    event_detects: Vec<EventDetect>,
+    // Fields are dropped in declaration order. The interrupts have to be removed
+    // before the pin is released, or they could remove a new owner's interrupts.
+    pub(crate) pin: Pin,
+}
+
+impl InputPin {
+    pub(crate) fn new(mut pin: Pin, pud_mode: PullUpDown) -> InputPin {
+        let prev_mode = pin.mode();
+
+        let prev_mode = if prev_mode == Mode::Input {
+            None
+        } else {
+            pin.set_mode(Mode::Input);
+            Some(prev_mode)
+        };
+
+        pin.set_pullupdown(pud_mode);
+
+        InputPin {
+            pin,
+            prev_mode,
+            reset_on_drop: true,
+            pud_mode,
+            interrupt: None,
+            async_interrupt: None,
+            event_detects: Vec::new(),
+        }
+    }
+
+    /// Configures a synchronous interrupt trigger.
+    ///
+    /// After configuring a synchronous interrupt trigger, call [`poll_interrupt`] to
+    /// block while waiting for a trigger event.
+    ///
+    /// Edges are detected by the kernel through a line event request on the
+    /// `/dev/gpiochipN` device that drives the SoC's GPIO pins, and are timestamped
+    /// when they occur rather than when they're read. Any previously configured
+    /// trigger is replaced.
+    ///
+    /// [`poll_interrupt`]: #method.poll_interrupt
+    pub fn set_interrupt(&mut self, trigger: Trigger) -> Result<()> {
+        self.clear_interrupt()?;
+        self.clear_async_interrupt()?;
+        self.interrupt = Some(Interrupt::new(self.pin(), trigger)?);
+
+        Ok(())
+    }
+
+    /// Removes a previously configured synchronous interrupt trigger.
+    pub fn clear_interrupt(&mut self) -> Result<()> {
+        self.interrupt = None;
+
+        Ok(())
+    }
+
+    /// Blocks until a synchronous interrupt is triggered, or the timeout is reached,
+    /// and returns the pin's logic level after the detected edge.
+    ///
+    /// Setting `reset` to `false` returns any cached interrupt trigger events if
+    /// available. Setting `reset` to `true` clears all cached events before polling
+    /// for new events.
+    ///
+    /// Setting `timeout` to `None` disables the timeout, so `poll_interrupt` blocks
+    /// until an interrupt is triggered. Returns `Ok(None)` when the timeout is reached,
+    /// or immediately if no interrupt trigger is configured.
+    ///
+    /// This method is not available for asynchronous interrupts.
+    pub fn poll_interrupt(&mut self, reset: bool, timeout: Option<Duration>) -> Result<Option<Level>> {
+        Ok(self.poll_event(reset, timeout)?.map(|event| event.level))
+    }
+
+    /// Blocks until a synchronous interrupt is triggered, or the timeout is reached,
+    /// and returns the detected [`Event`], including the kernel's timestamp.
+    ///
+    /// `reset` and `timeout` behave the same as for [`poll_interrupt`].
+    ///
+    /// [`Event`]: interrupt/struct.Event.html
+    /// [`poll_interrupt`]: #method.poll_interrupt
+    pub fn poll_event(&mut self, reset: bool, timeout: Option<Duration>) -> Result<Option<Event>> {
+        match self.interrupt {
+            Some(ref interrupt) => interrupt.poll(reset, timeout),
+            None => Ok(None),
+        }
+    }
+
+    /// Enables or disables one of the GPIO peripheral's event detect conditions
+    /// for the pin.
+    ///
+    /// Once the condition is met, the pin's event status bit stays set until it's
+    /// cleared through [`clear_event_detected`]. Unlike sampling the pin's level in a
+    /// polling loop, this catches short pulses that occur between two reads.
+    ///
+    /// Enabled conditions are disabled again when the `InputPin` goes out of scope,
+    /// regardless of the value of [`reset_on_drop`], as the pin's event status bit
+    /// would otherwise keep raising interrupts after it's released.
+    ///
+    /// ## Note
+    ///
+    /// The event status bits also raise the GPIO interrupt, which is handled by the
+    /// kernel. Enabling [`High`] or [`Low`] level detection on a pin the kernel isn't
+    /// expecting interrupts from can flood it with interrupts, and may freeze the
+    /// system. Use [`set_interrupt`] for edge detection where possible.
+    ///
+    /// [`clear_event_detected`]: #method.clear_event_detected
+    /// [`reset_on_drop`]: #method.set_reset_on_drop
+    /// [`High`]: enum.EventDetect.html#variant.High
+    /// [`Low`]: enum.EventDetect.html#variant.Low
+    /// [`set_interrupt`]: #method.set_interrupt
+    pub fn set_event_detect(&mut self, detect: EventDetect, enabled: bool) {
+        self.pin.set_event_detect(detect, enabled);
+
+        self.event_detects.retain(|&enabled_detect| enabled_detect != detect);
+        if enabled {
+            self.event_detects.push(detect);
+        }
+    }
+
+    /// Returns `true` if the event detect condition is enabled for the pin.
+    pub fn event_detect(&self, detect: EventDetect) -> bool {
+        self.pin.gpio_state.gpio_mem.event_detect(self.pin.pin, detect)
+    }
+
+    /// Returns `true` if any of the enabled event detect conditions was met since
+    /// the pin's event status bit was last cleared.
+    pub fn is_event_detected(&self) -> bool {
+        self.pin.gpio_state.gpio_mem.event_status(self.pin.pin)
+    }
+
+    /// Clears the pin's event status bit.
+    pub fn clear_event_detected(&mut self) {
+        self.pin.clear_event_detected();
+    }
+
+    pub(crate) fn interrupt(&self) -> Option<&Interrupt> {
+        self.interrupt.as_ref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_test_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Configures an asynchronous interrupt trigger, which executes the callback on a
+    /// separate thread when the interrupt is triggered.
+    ///
+    /// The callback closure or function pointer is called with a single [`Level`]
+    /// argument, the pin's logic level after the detected edge.
+    ///
+    /// A single background thread waits on the asynchronous interrupts of all pins,
+    /// so a callback should return quickly to avoid delaying the callbacks of other
+    /// pins. Any previously configured (a)synchronous interrupt trigger is replaced.
+    ///
+    /// If a callback panics, the thread stops, and the next call to
+    /// `set_async_interrupt` or [`clear_async_interrupt`] returns [`Error::ThreadPanic`].
+    ///
+    /// [`Level`]: enum.Level.html
+    /// [`clear_async_interrupt`]: #method.clear_async_interrupt
+    /// [`Error::ThreadPanic`]: enum.Error.html#variant.ThreadPanic
+    pub fn set_async_interrupt<C>(&mut self, trigger: Trigger, callback: C) -> Result<()>
+    where
+        C: FnMut(Level) + Send + 'static,
+    {
+        self.clear_interrupt()?;
+        self.clear_async_interrupt()?;
+
+        self.async_interrupt = Some(AsyncInterrupt::new(
+            self.pin(),
+            trigger,
+            self.pin.gpio_state.clone(),
+            callback,
+        )?);
+
+        Ok(())
+    }
+
+    /// Removes a previously configured asynchronous interrupt trigger.
+    ///
+    /// Once this returns, the callback won't be called again. The background
+    /// thread is stopped if no other pins have an asynchronous interrupt configured.
+    pub fn clear_async_interrupt(&mut self) -> Result<()> {
+        match self.async_interrupt.take() {
+            Some(async_interrupt) => async_interrupt.clear(),
+            None => Ok(()),
+        }
+    }
+
+    impl_pin!();
+
+    impl_input!();
+    impl_reset_on_drop!();
+}
+
+impl_drop!(InputPin, event_detects: event_detects);
+impl_eq!(InputPin);
 
 /// GPIO pin configured as output.
 ///
@@ -215,13 +657,35 @@ impl_eq!(Pin);
 /// [`PwmPin`]: ../../embedded_hal/trait.PwmPin.html
 #[derive(Debug)]
 pub struct OutputPin {
-    pin: Pin,
+    pub(crate) pin: Pin,
     prev_mode: Option<Mode>,
     reset_on_drop: bool,
+    pud_mode: PullUpDown,
+    drop_level: Option<Level>,
+    soft_pwm: Option<SoftPwm>,
 }
 
 impl OutputPin {
-    pub(crate) fn new(mut pin: Pin) -> OutputPin {
+    pub(crate) fn new(pin: Pin) -> OutputPin {
+        OutputPin::configure(pin, None)
+    }
+
+    /// Consumes the `Pin`, returns an `OutputPin` and sets its mode to [`Output`]
+    /// after setting its output state to `level`.
+    ///
+    /// The output latch is programmed while the pin is still an input, so the pin
+    /// never drives the wrong level, not even briefly, when it switches to [`Output`].
+    ///
+    /// [`Output`]: enum.Mode.html#variant.Output
+    pub fn with_initial_level(pin: Pin, level: Level) -> OutputPin {
+        OutputPin::configure(pin, Some(level))
+    }
+
+    fn configure(mut pin: Pin, level: Option<Level>) -> OutputPin {
+        if let Some(level) = level {
+            pin.write(level);
+        }
+
         let prev_mode = pin.mode();
 
         let prev_mode = if prev_mode == Mode::Output {
@@ -235,15 +699,411 @@ impl OutputPin {
             pin,
             prev_mode,
             reset_on_drop: true,
+            pud_mode: PullUpDown::Off,
+            drop_level: None,
+            soft_pwm: None,
         }
     }
 
+    /// Configures a software-based PWM signal.
+    ///
+    /// `period` indicates the time it takes to complete one cycle.
+    ///
+    /// `pulse_width` indicates the amount of time the PWM signal is active during a
+    /// single period, and is capped at `period`.
+    ///
+    /// Software-based PWM is implemented by toggling the pin's output state on a
+    /// background thread. Each phase is timed by sleeping until shortly before its
+    /// deadline and busy-waiting for the remainder, which keeps the timing within a
+    /// few microseconds at the cost of some CPU time. It's still affected by
+    /// scheduling, so for precise signals use the hardware PWM peripheral instead.
+    ///
+    /// Changing the output state through [`set_low`], [`set_high`], [`write`] or
+    /// [`toggle`] while PWM is active has no lasting effect. Call [`clear_pwm`] first.
+    ///
+    /// [`set_low`]: #method.set_low
+    /// [`set_high`]: #method.set_high
+    /// [`write`]: #method.write
+    /// [`toggle`]: #method.toggle
+    /// [`clear_pwm`]: #method.clear_pwm
+    pub fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> Result<()> {
+        let pulse_width = pulse_width.min(period);
+
+        match self.soft_pwm {
+            Some(ref mut soft_pwm) => soft_pwm.reconfigure(period, pulse_width),
+            None => {
+                self.soft_pwm = Some(SoftPwm::new(
+                    self.pin(),
+                    self.pin.gpio_state.clone(),
+                    period,
+                    pulse_width,
+                )?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Configures a software-based PWM signal.
+    ///
+    /// `set_pwm_frequency` is a convenience method that converts `frequency` to a period,
+    /// and calculates the pulse width based on the specified `duty_cycle`.
+    ///
+    /// `frequency` is specified in hertz (Hz).
+    ///
+    /// `duty_cycle` is specified as a floating point value between `0.0` (0%) and `1.0` (100%).
+    ///
+    /// Returns [`Error::InvalidFrequency`] if `frequency` isn't a finite value above
+    /// `0.0`, or [`Error::InvalidDutyCycle`] if `duty_cycle` isn't a finite value.
+    /// See [`set_pwm`] for more details.
+    ///
+    /// [`Error::InvalidFrequency`]: enum.Error.html#variant.InvalidFrequency
+    /// [`Error::InvalidDutyCycle`]: enum.Error.html#variant.InvalidDutyCycle
+    ///
+    /// [`set_pwm`]: #method.set_pwm
+    pub fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<()> {
+        let (period, pulse_width) = soft_pwm::frequency_to_period(frequency, duty_cycle)?;
+
+        self.set_pwm(period, pulse_width)
+    }
+
+    /// Stops a previously configured software-based PWM signal, and sets the pin to low.
+    ///
+    /// The thread is stopped automatically when the `OutputPin` goes out of scope.
+    pub fn clear_pwm(&mut self) -> Result<()> {
+        match self.soft_pwm.take() {
+            Some(mut soft_pwm) => soft_pwm.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value of `drop_level`.
+    pub fn drop_level(&self) -> Option<Level> {
+        self.drop_level
+    }
+
+    /// When set, drives the pin to the specified safe level when the pin goes out
+    /// of scope, before its mode is reset. By default, this is set to `None`.
+    ///
+    /// The level is written even if [`reset_on_drop`] is set to `false`, in which
+    /// case the pin keeps driving it after the `OutputPin` is dropped. This
+    /// makes sure relays and active-low devices end up in a defined state.
+    ///
+    /// [`reset_on_drop`]: #method.set_reset_on_drop
+    pub fn set_drop_level(&mut self, drop_level: Option<Level>) {
+        self.drop_level = drop_level;
+    }
+
     impl_pin!();
 
     impl_output!();
     impl_reset_on_drop!();
 }
 
-impl_drop!(OutputPin);
+impl_drop!(OutputPin, soft_pwm: soft_pwm, drop_level: drop_level);
 impl_eq!(OutputPin);
 
+/// GPIO pin that can be (re)configured for any mode or alternate function.
+///
+/// `IoPin`s are constructed by converting a [`Pin`] using [`Pin::into_io`].
+/// The pin's mode is automatically set to the specified mode.
+///
+/// An `IoPin` can be reconfigured for any available mode. Depending on the
+/// mode, some methods may not have any effect. For instance, calling a method that
+/// alters the pin's output state won't cause any changes when the pin's mode is set
+/// to [`Input`]. This makes it suitable for bidirectional protocols such as 1-Wire,
+/// where a pin quickly switches between driving the line and reading it.
+///
+/// [`Pin`]: struct.Pin.html
+/// [`Input`]: enum.Mode.html#variant.Input
+/// [`Pin::into_io`]: struct.Pin.html#method.into_io
+#[derive(Debug)]
+pub struct IoPin {
+    pin: Pin,
+    mode: Mode,
+    prev_mode: Option<Mode>,
+    reset_on_drop: bool,
+    pud_mode: PullUpDown,
+}
+
+impl IoPin {
+    pub(crate) fn new(mut pin: Pin, mode: Mode) -> IoPin {
+        let prev_mode = pin.mode();
+
+        let prev_mode = if prev_mode == mode {
+            None
+        } else {
+            pin.set_mode(mode);
+            Some(prev_mode)
+        };
+
+        IoPin {
+            pin,
+            mode,
+            prev_mode,
+            reset_on_drop: true,
+            pud_mode: PullUpDown::Off,
+        }
+    }
+
+    /// Returns the pin's mode.
+    #[inline]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Sets the pin's mode.
+    #[inline]
+    pub fn set_mode(&mut self, mode: Mode) {
+        // If prev_mode is None, the pin was already in the requested mode during
+        // construction. Save that mode, so it's restored when the IoPin is dropped.
+        if self.prev_mode.is_none() {
+            self.prev_mode = Some(self.mode);
+        }
+
+        self.mode = mode;
+        self.pin.set_mode(mode);
+    }
+
+    impl_pin!();
+
+    impl_input!();
+    impl_output!();
+    impl_reset_on_drop!();
+}
+
+impl_drop!(IoPin);
+impl_eq!(IoPin);
+
+/// GPIO pin configured for an alternate function.
+///
+/// `AltPin`s are constructed by converting a [`Pin`] using [`Pin::into_alt`].
+/// The pin's mode is automatically set to the specified alternate function mode,
+/// which hands control of the pin over to a peripheral such as PWM, SPI or UART.
+///
+/// [`Pin`]: struct.Pin.html
+/// [`Pin::into_alt`]: struct.Pin.html#method.into_alt
+#[derive(Debug)]
+pub struct AltPin {
+    pin: Pin,
+    mode: Mode,
+    prev_mode: Option<Mode>,
+    reset_on_drop: bool,
+    pud_mode: PullUpDown,
+}
+
+impl AltPin {
+    pub(crate) fn new(mut pin: Pin, mode: Mode) -> Result<AltPin> {
+        if !alt::ALT_MODES.contains(&mode) {
+            return Err(Error::InvalidAltMode(mode));
+        }
+
+        let prev_mode = pin.mode();
+
+        let prev_mode = if prev_mode == mode {
+            None
+        } else {
+            pin.set_mode(mode);
+            Some(prev_mode)
+        };
+
+        Ok(AltPin {
+            pin,
+            mode,
+            prev_mode,
+            reset_on_drop: true,
+            pud_mode: PullUpDown::Off,
+        })
+    }
+
+    /// Returns the pin's alternate function mode.
+    #[inline]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the peripheral function the pin carries, such as `PWM0_0`.
+    ///
+    /// Returns `None` if the alternate function is reserved, or unknown for this SoC.
+    #[inline]
+    pub fn function(&self) -> Option<&'static str> {
+        self.pin.function()
+    }
+
+    impl_pin!();
+
+    impl_input!();
+    impl_reset_on_drop!();
+}
+
+impl_drop!(AltPin);
+impl_eq!(AltPin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::mem;
+    use crate::gpio::tests::{recorded, simulated};
+
+    #[test]
+    fn output_pin_blinks_and_restores_mode() {
+        let (gpio, sim) = simulated();
+
+        let mut led = gpio.get(3).unwrap().into_output();
+        assert_eq!(sim.mode(3).unwrap(), Mode::Output);
+        for _ in 0..3 {
+            led.set_high();
+            assert_eq!(sim.level(3).unwrap(), Level::High);
+            led.set_low();
+            assert_eq!(sim.level(3).unwrap(), Level::Low);
+        }
+
+        drop(led);
+        assert_eq!(sim.mode(3).unwrap(), Mode::Input);
+    }
+
+    #[test]
+    fn input_pin_reads_external_level_and_pull() {
+        let (gpio, sim) = simulated();
+
+        let button = gpio.get(26).unwrap().into_input_pullup();
+        assert!(button.is_high());
+        sim.set_level(26, Level::Low).unwrap();
+        assert!(button.is_low());
+
+        drop(button);
+        assert_eq!(sim.pullupdown(26).unwrap(), PullUpDown::Off);
+    }
+
+    #[test]
+    fn io_pin_switches_direction_and_restores_mode() {
+        let (gpio, sim) = simulated();
+
+        let mut data = gpio.get(4).unwrap().into_io(Mode::Output);
+        data.set_low();
+        assert_eq!(sim.level(4).unwrap(), Level::Low);
+
+        data.set_mode(Mode::Input);
+        sim.set_level(4, Level::High).unwrap();
+        assert_eq!(data.read(), Level::High);
+
+        data.set_mode(Mode::Output);
+        data.write(Level::Low);
+        assert_eq!(data.read(), Level::Low);
+
+        drop(data);
+        assert_eq!(sim.mode(4).unwrap(), Mode::Input);
+    }
+
+    #[test]
+    fn alt_pin_selects_function_and_restores_mode() {
+        let (gpio, sim) = simulated();
+
+        let pwm = gpio.get(18).unwrap().into_alt(Mode::Alt5).unwrap();
+        assert_eq!(sim.mode(18).unwrap(), Mode::Alt5);
+        assert_eq!(pwm.function(), Some("PWM0_0"));
+        assert_eq!(gpio.gpio_mem().describe(18), "Alt5 (PWM0_0)");
+
+        drop(pwm);
+        assert_eq!(sim.mode(18).unwrap(), Mode::Input);
+    }
+
+    #[test]
+    fn alt_pin_rejects_input_and_output() {
+        let (gpio, sim) = simulated();
+
+        for &mode in [Mode::Input, Mode::Output].iter() {
+            assert!(matches!(
+                gpio.get(18).unwrap().into_alt(mode),
+                Err(Error::InvalidAltMode(m)) if m == mode
+            ));
+        }
+        assert_eq!(sim.mode(18).unwrap(), Mode::Input);
+        assert!(gpio.get(18).is_ok());
+    }
+
+    #[test]
+    fn output_pin_writes_toggles_and_reads_back() {
+        let (gpio, sim) = simulated();
+
+        let mut led = gpio.get(21).unwrap().into_output();
+        led.write(Level::High);
+        assert!(led.is_set_high());
+        assert_eq!(sim.level(21).unwrap(), Level::High);
+
+        led.toggle();
+        assert!(led.is_set_low());
+        assert_eq!(sim.level(21).unwrap(), Level::Low);
+
+        led.toggle();
+        assert!(led.is_set_high());
+    }
+
+    #[test]
+    fn output_pin_initial_level_is_set_before_mode() {
+        let (gpio, _sim, writes) = recorded();
+
+        let relay = OutputPin::with_initial_level(gpio.get(12).unwrap(), Level::High);
+        assert!(relay.is_set_high());
+        assert_eq!(
+            *writes.lock().unwrap(),
+            vec![mem::GPSET0, mem::GPFSEL0 + 1]
+        );
+    }
+
+    #[test]
+    fn output_pin_drives_drop_level_before_reset() {
+        let (gpio, sim) = simulated();
+
+        let mut relay = gpio.get(6).unwrap().into_output_low();
+        assert_eq!(sim.level(6).unwrap(), Level::Low);
+        relay.set_drop_level(Some(Level::High));
+        relay.set_reset_on_drop(false);
+
+        drop(relay);
+        assert_eq!(sim.mode(6).unwrap(), Mode::Output);
+        assert_eq!(sim.level(6).unwrap(), Level::High);
+    }
+
+    #[test]
+    fn input_pin_event_detect_catches_short_pulses() {
+        let (gpio, sim) = simulated();
+
+        let mut sensor = gpio.get(23).unwrap().into_input();
+        sensor.set_event_detect(EventDetect::AsyncRisingEdge, true);
+        assert!(sensor.event_detect(EventDetect::AsyncRisingEdge));
+        assert!(!sensor.event_detect(EventDetect::RisingEdge));
+
+        // The pulse is over before the pin is sampled again.
+        sim.set_level(23, Level::High).unwrap();
+        sim.set_level(23, Level::Low).unwrap();
+        assert!(sensor.is_low());
+        assert!(sensor.is_event_detected());
+
+        sensor.clear_event_detected();
+        assert!(!sensor.is_event_detected());
+
+        sensor.set_event_detect(EventDetect::FallingEdge, true);
+        sim.set_level(23, Level::High).unwrap();
+        drop(sensor);
+        assert!(!gpio.gpio_mem().event_detect(23, EventDetect::AsyncRisingEdge));
+        assert!(!gpio.gpio_mem().event_detect(23, EventDetect::FallingEdge));
+        assert!(!gpio.gpio_mem().event_status(23));
+    }
+
+    #[test]
+    fn input_pin_event_detect_is_disabled_without_reset_on_drop() {
+        let (gpio, sim) = simulated();
+
+        let mut sensor = gpio.get(24).unwrap().into_input_pullup();
+        sensor.set_reset_on_drop(false);
+        sensor.set_event_detect(EventDetect::High, true);
+        sim.set_level(24, Level::High).unwrap();
+        assert!(sensor.is_event_detected());
+
+        drop(sensor);
+        assert!(!gpio.gpio_mem().event_detect(24, EventDetect::High));
+        assert!(!gpio.gpio_mem().event_status(24));
+        assert_eq!(sim.pullupdown(24).unwrap(), PullUpDown::PullUp);
+    }
+}
@@ -0,0 +1,422 @@
+use std::sync::{Arc, Mutex};
+
+use crate::gpio::mem::{
+    mode_from_bits, RegisterBackend, GPAFEN0, GPAREN0, GPCLR0, GPEDS0, GPFEN0, GPFSEL0, GPHEN0,
+    GPIO_MEM_REGISTERS, GPLEN0, GPLEV0, GPPUD, GPPUDCLK0, GPPUD_CNTRL_REG0, GPREN0, GPSET0,
+};
+use crate::gpio::pin::MAX;
+use crate::gpio::{Error, Level, Mode, PullUpDown, Result};
+
+// Bits 0-53 of a 64-bit bank pair.
+const PIN_MASK: u64 = (1 << MAX) - 1;
+// Second register of each bank pair. Match patterns can't contain expressions.
+const GPSET1: usize = GPSET0 + 1;
+const GPCLR1: usize = GPCLR0 + 1;
+const GPLEV1: usize = GPLEV0 + 1;
+const GPEDS1: usize = GPEDS0 + 1;
+const GPPUDCLK1: usize = GPPUDCLK0 + 1;
+
+/// Simulated GPIO peripheral.
+///
+/// `SimulatedGpio` is a [`RegisterBackend`] that models the GPIO registers in
+/// memory, so code built on [`Gpio`] can run on CI or a developer's laptop.
+/// It's selected by passing it to [`Gpio::with_backend`].
+///
+/// The simulation covers:
+///
+/// * the GPFSEL function select fields,
+/// * GPSET/GPCLR driving the GPLEV level of pins configured as output,
+/// * input levels driven from outside through [`set_level`],
+/// * the pull-up/pull-down resistors of floating inputs, configured either through
+///   GPPUD_CNTRL_REG (BCM2711) or the GPPUD/GPPUDCLK sequence (older SoCs),
+/// * rising/falling edge and high/low level event detection, reported in GPEDS.
+///
+/// Clones share the same state, so keep a clone around to drive inputs and
+/// check outputs after handing the `SimulatedGpio` over to `Gpio`.
+///
+/// [`RegisterBackend`]: ../mem/trait.RegisterBackend.html
+/// [`Gpio`]: ../struct.Gpio.html
+/// [`Gpio::with_backend`]: ../struct.Gpio.html#method.with_backend
+/// [`set_level`]: #method.set_level
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedGpio {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    // Values written to the registers that are simply stored, such as GPFSEL
+    // and the event detect enables.
+    registers: [u32; GPIO_MEM_REGISTERS],
+    // Output latch, set through GPSET and cleared through GPCLR.
+    output: u64,
+    // Levels driven from outside. Only valid for pins set in `driven`.
+    input: u64,
+    driven: u64,
+    pud: [PullUpDown; MAX],
+    // Levels as of the last update, used for edge detection.
+    levels: u64,
+    // Event detect status (GPEDS0/1).
+    events: u64,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State {
+            registers: [0; GPIO_MEM_REGISTERS],
+            output: 0,
+            input: 0,
+            driven: 0,
+            pud: [PullUpDown::Off; MAX],
+            levels: 0,
+            events: 0,
+        }
+    }
+}
+
+impl State {
+    fn bank_pair(&self, offset: usize) -> u64 {
+        u64::from(self.registers[offset]) | (u64::from(self.registers[offset + 1]) << 32)
+    }
+
+    fn mode(&self, pin: u8) -> Mode {
+        mode_from_bits(self.registers[GPFSEL0 + (pin / 10) as usize] >> ((pin % 10) * 3))
+    }
+
+    fn compute_levels(&self) -> u64 {
+        (0..MAX as u8).fold(0, |levels, pin| {
+            let bit = 1u64 << pin;
+
+            let high = if self.mode(pin) == Mode::Output {
+                self.output & bit != 0
+            } else if self.driven & bit != 0 {
+                self.input & bit != 0
+            } else {
+                self.pud[pin as usize] == PullUpDown::PullUp
+            };
+
+            if high {
+                levels | bit
+            } else {
+                levels
+            }
+        })
+    }
+
+    // Recomputes the pin levels, and latches any detected events.
+    fn update(&mut self) {
+        let levels = self.compute_levels();
+        let rising = !self.levels & levels;
+        let falling = self.levels & !levels;
+
+        self.events |= rising & (self.bank_pair(GPREN0) | self.bank_pair(GPAREN0));
+        self.events |= falling & (self.bank_pair(GPFEN0) | self.bank_pair(GPAFEN0));
+        self.events |= levels & self.bank_pair(GPHEN0);
+        self.events |= !levels & self.bank_pair(GPLEN0);
+        self.events &= PIN_MASK;
+        self.levels = levels;
+    }
+
+    // Applies the GPPUD value to the pins selected through a GPPUDCLK write.
+    fn clock_pud(&mut self, bank: usize, value: u32) {
+        let pud = match self.registers[GPPUD] & 0b11 {
+            0b01 => PullUpDown::PullDown,
+            0b10 => PullUpDown::PullUp,
+            _ => PullUpDown::Off,
+        };
+
+        for shift in 0..32 {
+            let pin = bank * 32 + shift;
+            if value & (1 << shift) != 0 && pin < MAX {
+                self.pud[pin] = pud;
+            }
+        }
+    }
+
+    // Applies a GPPUD_CNTRL_REG write, which holds the 2-bit fields of 16 pins.
+    fn control_pud(&mut self, reg: usize, value: u32) {
+        for field in 0..16 {
+            let pin = reg * 16 + field;
+            if pin < MAX {
+                self.pud[pin] = match (value >> (field * 2)) & 0b11 {
+                    0b01 => PullUpDown::PullUp,
+                    0b10 => PullUpDown::PullDown,
+                    _ => PullUpDown::Off,
+                };
+            }
+        }
+    }
+}
+
+// Rejects pins the GPIO peripheral doesn't expose, the same way Gpio::get does.
+fn check_pin(pin: u8) -> Result<u8> {
+    if pin as usize >= MAX {
+        return Err(Error::PinNotAvailable(pin));
+    }
+
+    Ok(pin)
+}
+
+impl SimulatedGpio {
+    /// Constructs a new `SimulatedGpio` with all pins configured as floating inputs.
+    pub fn new() -> SimulatedGpio {
+        SimulatedGpio::default()
+    }
+
+    /// Drives the pin's input level from outside, as if it were connected to
+    /// another device.
+    ///
+    /// The level is ignored while the pin is configured as an output.
+    ///
+    /// Returns [`Error::PinNotAvailable`] if the pin doesn't exist, as do the other
+    /// methods that take a pin number.
+    ///
+    /// [`Error::PinNotAvailable`]: ../enum.Error.html#variant.PinNotAvailable
+    pub fn set_level(&self, pin: u8, level: Level) -> Result<()> {
+        let bit = 1u64 << check_pin(pin)?;
+        let mut state = self.state.lock().unwrap();
+
+        state.driven |= bit;
+        match level {
+            Level::Low => state.input &= !bit,
+            Level::High => state.input |= bit,
+        }
+        state.update();
+
+        Ok(())
+    }
+
+    /// Stops driving the pin from outside. Its level is then determined by
+    /// the pull-up/pull-down resistor, and reads as [`Low`] if that's disabled.
+    ///
+    /// [`Low`]: ../enum.Level.html#variant.Low
+    pub fn release(&self, pin: u8) -> Result<()> {
+        let bit = 1u64 << check_pin(pin)?;
+        let mut state = self.state.lock().unwrap();
+
+        state.driven &= !bit;
+        state.update();
+
+        Ok(())
+    }
+
+    /// Returns the pin's current logic level.
+    pub fn level(&self, pin: u8) -> Result<Level> {
+        let bit = 1u64 << check_pin(pin)?;
+        let mut state = self.state.lock().unwrap();
+        state.update();
+
+        Ok(if state.levels & bit == 0 {
+            Level::Low
+        } else {
+            Level::High
+        })
+    }
+
+    /// Returns the pin's mode.
+    pub fn mode(&self, pin: u8) -> Result<Mode> {
+        let pin = check_pin(pin)?;
+
+        Ok(self.state.lock().unwrap().mode(pin))
+    }
+
+    /// Returns the pin's pull-up/pull-down resistor state.
+    pub fn pullupdown(&self, pin: u8) -> Result<PullUpDown> {
+        let pin = check_pin(pin)?;
+
+        Ok(self.state.lock().unwrap().pud[pin as usize])
+    }
+}
+
+impl RegisterBackend for SimulatedGpio {
+    fn read(&self, offset: usize) -> u32 {
+        if offset >= GPIO_MEM_REGISTERS {
+            return 0;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.update();
+
+        match offset {
+            // GPSET and GPCLR are write-only.
+            GPSET0 | GPSET1 | GPCLR0 | GPCLR1 => 0,
+            GPLEV0 => state.levels as u32,
+            GPLEV1 => (state.levels >> 32) as u32,
+            GPEDS0 => state.events as u32,
+            GPEDS1 => (state.events >> 32) as u32,
+            _ => state.registers[offset],
+        }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        if offset >= GPIO_MEM_REGISTERS {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let value64 = u64::from(value);
+
+        match offset {
+            GPSET0 => state.output |= value64,
+            GPSET1 => state.output |= value64 << 32,
+            GPCLR0 => state.output &= !value64,
+            GPCLR1 => state.output &= !(value64 << 32),
+            // GPLEV is read-only.
+            GPLEV0 | GPLEV1 => {}
+            // Writing a 1 clears the event detect status bit.
+            GPEDS0 => state.events &= !value64,
+            GPEDS1 => state.events &= !(value64 << 32),
+            GPPUDCLK0 | GPPUDCLK1 => {
+                state.registers[offset] = value;
+                state.clock_pud(offset - GPPUDCLK0, value);
+            }
+            _ if (GPPUD_CNTRL_REG0..GPPUD_CNTRL_REG0 + 4).contains(&offset) => {
+                state.registers[offset] = value;
+                state.control_pud(offset - GPPUD_CNTRL_REG0, value);
+            }
+            _ => state.registers[offset] = value,
+        }
+
+        state.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::mem::GpioMem;
+    use crate::gpio::EventDetect;
+    use crate::system::SoC;
+
+    fn gpio_mem(sim: &SimulatedGpio, soc: SoC) -> GpioMem {
+        GpioMem::with_backend(Box::new(sim.clone()), soc)
+    }
+
+    #[test]
+    fn outputs_drive_gplev() {
+        let sim = SimulatedGpio::new();
+        let gpio_mem = gpio_mem(&sim, SoC::Bcm2711);
+
+        for pin in 0..MAX as u8 {
+            gpio_mem.set_mode(pin, Mode::Output);
+            gpio_mem.set_high(pin);
+            assert_eq!(gpio_mem.level(pin), Level::High, "pin {}", pin);
+            gpio_mem.set_low(pin);
+            assert_eq!(gpio_mem.level(pin), Level::Low, "pin {}", pin);
+        }
+    }
+
+    #[test]
+    fn inputs_follow_external_level_then_pull() {
+        let sim = SimulatedGpio::new();
+        let gpio_mem = gpio_mem(&sim, SoC::Bcm2711);
+
+        sim.set_level(40, Level::High).unwrap();
+        assert_eq!(gpio_mem.level(40), Level::High);
+
+        // Output latch is ignored while the pin is an input.
+        gpio_mem.set_low(40);
+        assert_eq!(gpio_mem.level(40), Level::High);
+
+        sim.release(40).unwrap();
+        assert_eq!(gpio_mem.level(40), Level::Low);
+
+        gpio_mem.set_pullupdown(40, PullUpDown::PullUp);
+        assert_eq!(gpio_mem.level(40), Level::High);
+    }
+
+    #[test]
+    fn pullupdown_through_both_register_layouts() {
+        for &soc in [SoC::Bcm2711, SoC::Bcm2835].iter() {
+            let sim = SimulatedGpio::new();
+            let gpio_mem = gpio_mem(&sim, soc);
+
+            gpio_mem.set_pullupdown(17, PullUpDown::PullUp);
+            gpio_mem.set_pullupdown(35, PullUpDown::PullDown);
+            assert_eq!(sim.pullupdown(17).unwrap(), PullUpDown::PullUp, "{}", soc);
+            assert_eq!(sim.pullupdown(35).unwrap(), PullUpDown::PullDown, "{}", soc);
+
+            gpio_mem.set_pullupdown(17, PullUpDown::Off);
+            assert_eq!(sim.pullupdown(17).unwrap(), PullUpDown::Off, "{}", soc);
+            assert_eq!(sim.pullupdown(35).unwrap(), PullUpDown::PullDown, "{}", soc);
+        }
+    }
+
+    #[test]
+    fn edge_detection_latches_until_cleared() {
+        let sim = SimulatedGpio::new();
+        sim.write(GPREN0, 1 << 4);
+
+        sim.set_level(4, Level::High).unwrap();
+        sim.set_level(4, Level::Low).unwrap();
+        assert_eq!(sim.read(GPEDS0), 1 << 4);
+
+        sim.write(GPEDS0, 1 << 4);
+        assert_eq!(sim.read(GPEDS0), 0);
+    }
+
+    #[test]
+    fn rejects_unavailable_pins() {
+        let sim = SimulatedGpio::new();
+
+        for &pin in [MAX as u8, 64, u8::MAX].iter() {
+            assert!(matches!(
+                sim.set_level(pin, Level::High),
+                Err(Error::PinNotAvailable(p)) if p == pin
+            ));
+            assert!(matches!(sim.release(pin), Err(Error::PinNotAvailable(_))));
+            assert!(matches!(sim.level(pin), Err(Error::PinNotAvailable(_))));
+            assert!(matches!(sim.mode(pin), Err(Error::PinNotAvailable(_))));
+            assert!(matches!(sim.pullupdown(pin), Err(Error::PinNotAvailable(_))));
+        }
+
+        sim.set_level(MAX as u8 - 1, Level::High).unwrap();
+        assert_eq!(sim.level(MAX as u8 - 1).unwrap(), Level::High);
+    }
+
+    #[test]
+    fn ignores_offsets_past_the_register_block() {
+        let sim = SimulatedGpio::new();
+
+        sim.write(GPIO_MEM_REGISTERS, 0xffff_ffff);
+        assert_eq!(sim.read(GPIO_MEM_REGISTERS), 0);
+        assert_eq!(sim.read(usize::MAX), 0);
+        assert!((0..GPIO_MEM_REGISTERS).all(|offset| sim.read(offset) == 0));
+    }
+
+    #[test]
+    fn event_detect_registers_through_gpio_mem() {
+        let sim = SimulatedGpio::new();
+        let gpio_mem = gpio_mem(&sim, SoC::Bcm2835);
+        let detects = [
+            (EventDetect::RisingEdge, GPREN0),
+            (EventDetect::FallingEdge, GPFEN0),
+            (EventDetect::High, GPHEN0),
+            (EventDetect::Low, GPLEN0),
+            (EventDetect::AsyncRisingEdge, GPAREN0),
+            (EventDetect::AsyncFallingEdge, GPAFEN0),
+        ];
+
+        for &(detect, register) in detects.iter() {
+            gpio_mem.set_event_detect(3, detect, true);
+            gpio_mem.set_event_detect(35, detect, true);
+            assert_eq!(sim.read(register), 1 << 3, "{}", detect);
+            assert_eq!(sim.read(register + 1), 1 << 3, "{}", detect);
+            assert!(gpio_mem.event_detect(35, detect));
+
+            gpio_mem.set_event_detect(3, detect, false);
+            assert_eq!(sim.read(register), 0, "{}", detect);
+            assert!(gpio_mem.event_detect(35, detect));
+            gpio_mem.set_event_detect(35, detect, false);
+        }
+
+        gpio_mem.set_event_detect(35, EventDetect::High, true);
+        sim.set_level(35, Level::High).unwrap();
+        gpio_mem.set_event_detect(35, EventDetect::High, false);
+        assert!(gpio_mem.event_status(35));
+        assert_eq!(gpio_mem.event_status_bank(1), 1 << 3);
+
+        gpio_mem.clear_event_status(35);
+        assert!(!gpio_mem.event_status(35));
+    }
+}
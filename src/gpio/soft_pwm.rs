@@ -0,0 +1,593 @@
+use std::hint;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::gpio::mem::GpioMem;
+use crate::gpio::pin::OutputPin;
+use crate::gpio::{self, Error, GpioState, Result};
+
+// Sleeping tends to overshoot by tens of microseconds, so the last part of
+// every wait is spent busy-waiting instead.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(200);
+
+#[derive(Debug)]
+enum Msg {
+    Reconfigure(Duration, Duration),
+    Stop,
+}
+
+// Waits until `deadline`, and returns the message that cut the wait short, if any.
+fn wait_until(receiver: &Receiver<Msg>, deadline: Instant) -> Option<Msg> {
+    let now = Instant::now();
+
+    if deadline > now + SPIN_THRESHOLD {
+        match receiver.recv_timeout(deadline - now - SPIN_THRESHOLD) {
+            Ok(msg) => return Some(msg),
+            Err(RecvTimeoutError::Disconnected) => return Some(Msg::Stop),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+
+    spin_until(deadline);
+
+    None
+}
+
+fn spin_until(deadline: Instant) {
+    while Instant::now() < deadline {
+        hint::spin_loop();
+    }
+}
+
+// Returns the deadline that follows `deadline`. Deadlines are scheduled back to
+// back, so timing errors don't add up. If the thread fell more than a `period`
+// behind, it starts over from now instead.
+pub(crate) fn next_deadline(deadline: Instant, period: Duration) -> Instant {
+    let next = deadline + period;
+    let now = Instant::now();
+
+    if now > next + period {
+        now
+    } else {
+        next
+    }
+}
+
+/// Converts `duty_cycle` to a pulse width within `period`. Finite values outside
+/// `0.0..=1.0` are clamped.
+pub(crate) fn duty_cycle_to_pulse_width(period: Duration, duty_cycle: f64) -> Result<Duration> {
+    if !duty_cycle.is_finite() {
+        return Err(Error::InvalidDutyCycle(duty_cycle));
+    }
+
+    Ok(period.mul_f64(duty_cycle.clamp(0.0, 1.0)))
+}
+
+/// Converts `frequency` and `duty_cycle` to a period and pulse width.
+pub(crate) fn frequency_to_period(frequency: f64, duty_cycle: f64) -> Result<(Duration, Duration)> {
+    if !frequency.is_finite() || frequency <= 0.0 {
+        return Err(Error::InvalidFrequency(frequency));
+    }
+
+    let period = Duration::try_from_secs_f64(1.0 / frequency)
+        .map_err(|_| Error::InvalidFrequency(frequency))?;
+
+    Ok((period, duty_cycle_to_pulse_width(period, duty_cycle)?))
+}
+
+/// Software PWM for a single pin, driven by a background thread.
+#[derive(Debug)]
+pub(crate) struct SoftPwm {
+    thread: Option<JoinHandle<()>>,
+    sender: Sender<Msg>,
+}
+
+impl SoftPwm {
+    pub(crate) fn new(
+        pin: u8,
+        gpio_state: Arc<GpioState>,
+        period: Duration,
+        pulse_width: Duration,
+    ) -> Result<SoftPwm> {
+        let (sender, receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name(format!("soft-pwm-{}", pin))
+            .spawn(move || SoftPwm::run(pin, &gpio_state, &receiver, period, pulse_width))?;
+
+        Ok(SoftPwm {
+            thread: Some(thread),
+            sender,
+        })
+    }
+
+    fn run(
+        pin: u8,
+        gpio_state: &GpioState,
+        receiver: &Receiver<Msg>,
+        mut period: Duration,
+        mut pulse_width: Duration,
+    ) {
+        let gpio_mem = &gpio_state.gpio_mem;
+        let mut start = Instant::now();
+
+        loop {
+            let msg = if period.is_zero() {
+                gpio_mem.set_low(pin);
+                Some(receiver.recv().unwrap_or(Msg::Stop))
+            } else {
+                if !pulse_width.is_zero() {
+                    gpio_mem.set_high(pin);
+                }
+
+                if pulse_width < period {
+                    wait_until(receiver, start + pulse_width).or_else(|| {
+                        gpio_mem.set_low(pin);
+                        wait_until(receiver, start + period)
+                    })
+                } else {
+                    wait_until(receiver, start + period)
+                }
+            };
+
+            match msg {
+                Some(Msg::Reconfigure(new_period, new_pulse_width)) => {
+                    period = new_period;
+                    pulse_width = new_pulse_width;
+                    start = Instant::now();
+                }
+                Some(Msg::Stop) => break,
+                None => start = next_deadline(start, period),
+            }
+        }
+
+        gpio_mem.set_low(pin);
+    }
+
+    /// Changes the period and pulse width, starting a new period right away.
+    pub(crate) fn reconfigure(&mut self, period: Duration, pulse_width: Duration) {
+        let _ = self.sender.send(Msg::Reconfigure(period, pulse_width));
+    }
+
+    /// Stops the thread, which leaves the pin set to low.
+    pub(crate) fn stop(&mut self) -> Result<()> {
+        let _ = self.sender.send(Msg::Stop);
+
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| Error::ThreadPanic),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SoftPwm {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Period error statistics of a [`SoftPwmController`].
+///
+/// The period error is the absolute difference between the measured time from
+/// the start of one period to the start of the next, and the configured period.
+///
+/// [`SoftPwmController`]: struct.SoftPwmController.html
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct JitterStats {
+    /// Number of measured periods.
+    pub periods: u64,
+    /// Smallest period error.
+    pub min: Duration,
+    /// Largest period error.
+    pub max: Duration,
+    /// Mean period error.
+    pub mean: Duration,
+}
+
+#[derive(Debug, Default)]
+struct JitterAccumulator {
+    periods: u64,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl JitterAccumulator {
+    fn record(&mut self, error: Duration) {
+        if self.periods == 0 || error < self.min {
+            self.min = error;
+        }
+        self.max = self.max.max(error);
+        self.total += error;
+        self.periods += 1;
+    }
+
+    fn stats(&self) -> JitterStats {
+        JitterStats {
+            periods: self.periods,
+            min: self.min,
+            max: self.max,
+            mean: if self.periods == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_nanos((self.total.as_nanos() / u128::from(self.periods)) as u64)
+            },
+        }
+    }
+}
+
+// State shared between the controller and its timer thread.
+#[derive(Debug)]
+struct Shared {
+    // Pulse width of each channel, in nanoseconds.
+    pulse_widths: Vec<AtomicU64>,
+    jitter: Mutex<JitterAccumulator>,
+}
+
+/// Multi-channel software PWM driven by a single timer thread.
+///
+/// A `SoftPwmController` takes ownership of several [`OutputPin`]s, called channels,
+/// that share the same period. Rather than running a thread per pin, a single
+/// thread sets all active channels with one GPSET write per bank at the start of
+/// each period, and clears channels whose pulses end at the same time with one
+/// GPCLR write per bank.
+///
+/// Duty cycles can be changed from other threads through a shared reference, for
+/// instance by wrapping the controller in an `Arc`. Changes take effect at the
+/// start of the next period.
+///
+/// [`OutputPin`]: struct.OutputPin.html
+#[derive(Debug)]
+pub struct SoftPwmController {
+    pins: Vec<OutputPin>,
+    period: Duration,
+    shared: Arc<Shared>,
+    stop: Option<Sender<Msg>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SoftPwmController {
+    /// Constructs a new `SoftPwmController`, and starts its timer thread.
+    ///
+    /// Each pin in `pins` becomes a channel, numbered by its index. All channels
+    /// start with a duty cycle of `0.0`, and any software PWM configured through
+    /// [`OutputPin::set_pwm`] is stopped.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the channels don't all belong to the same [`Gpio`] instance.
+    ///
+    /// [`OutputPin::set_pwm`]: struct.OutputPin.html#method.set_pwm
+    /// [`Gpio`]: struct.Gpio.html
+    pub fn new(mut pins: Vec<OutputPin>, period: Duration) -> Result<SoftPwmController> {
+        gpio::assert_same_gpio(pins.iter().map(|pin| &pin.pin.gpio_state));
+
+        for pin in pins.iter_mut() {
+            pin.clear_pwm()?;
+        }
+
+        let shared = Arc::new(Shared {
+            pulse_widths: pins.iter().map(|_| AtomicU64::new(0)).collect(),
+            jitter: Mutex::new(JitterAccumulator::default()),
+        });
+        let (sender, receiver) = mpsc::channel();
+
+        // Without a period there's nothing to time, and all channels stay low.
+        let thread = match pins.first() {
+            Some(first) if !period.is_zero() => {
+                let gpio_state = first.pin.gpio_state.clone();
+                let channels: Vec<u8> = pins.iter().map(|pin| pin.pin()).collect();
+                let shared = shared.clone();
+
+                Some(
+                    thread::Builder::new()
+                        .name("soft-pwm-controller".to_owned())
+                        .spawn(move || {
+                            SoftPwmController::run(
+                                &gpio_state,
+                                &channels,
+                                period,
+                                &shared,
+                                &receiver,
+                            )
+                        })?,
+                )
+            }
+            _ => None,
+        };
+
+        Ok(SoftPwmController {
+            pins,
+            period,
+            shared,
+            stop: Some(sender),
+            thread,
+        })
+    }
+
+    fn run(
+        gpio_state: &GpioState,
+        channels: &[u8],
+        period: Duration,
+        shared: &Shared,
+        receiver: &Receiver<Msg>,
+    ) {
+        let gpio_mem = &gpio_state.gpio_mem;
+        let mut start = Instant::now();
+        let mut prev_start: Option<Instant> = None;
+
+        // The only message sent to the thread is Msg::Stop, which can cut any wait short.
+        'periods: loop {
+            if wait_until(receiver, start).is_some() {
+                break 'periods;
+            }
+
+            let now = Instant::now();
+            if let Some(prev_start) = prev_start {
+                let measured = now - prev_start;
+                shared.jitter.lock().unwrap().record(measured.abs_diff(period));
+            }
+            prev_start = Some(now);
+
+            // Pulses shorter than the period, sorted by the time they end.
+            let mut pulses: Vec<(Duration, u8)> = Vec::with_capacity(channels.len());
+            let mut set = [0u32; GpioMem::BANKS];
+            let mut clear = [0u32; GpioMem::BANKS];
+
+            for (channel, &pin) in channels.iter().enumerate() {
+                let pulse_width =
+                    Duration::from_nanos(shared.pulse_widths[channel].load(Ordering::Relaxed));
+                let bit = 1 << (pin % 32);
+
+                if pulse_width.is_zero() {
+                    clear[pin as usize / 32] |= bit;
+                } else {
+                    set[pin as usize / 32] |= bit;
+                    if pulse_width < period {
+                        pulses.push((pulse_width, pin));
+                    }
+                }
+            }
+            pulses.sort_unstable();
+
+            for bank in 0..GpioMem::BANKS {
+                gpio_mem.write_bank(bank, set[bank], clear[bank]);
+            }
+
+            let mut pulses = pulses.as_slice();
+            while let Some(&(pulse_width, _)) = pulses.first() {
+                let ending = pulses.iter().take_while(|&&(end, _)| end == pulse_width).count();
+
+                let mut clear = [0u32; GpioMem::BANKS];
+                for &(_, pin) in &pulses[..ending] {
+                    clear[pin as usize / 32] |= 1 << (pin % 32);
+                }
+
+                if wait_until(receiver, start + pulse_width).is_some() {
+                    break 'periods;
+                }
+                for (bank, &clear) in clear.iter().enumerate() {
+                    gpio_mem.write_bank(bank, 0, clear);
+                }
+
+                pulses = &pulses[ending..];
+            }
+
+            let next = next_deadline(start, period);
+            if next != start + period {
+                // The time lost catching up isn't jitter.
+                prev_start = None;
+            }
+            start = next;
+        }
+
+        for &pin in channels {
+            gpio_mem.set_low(pin);
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn channels(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Returns the pins, indexed by channel.
+    pub fn pins(&self) -> &[OutputPin] {
+        &self.pins
+    }
+
+    /// Returns the period shared by all channels.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns the channel's duty cycle.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn duty_cycle(&self, channel: usize) -> f64 {
+        let pulse_width = self.shared.pulse_widths[channel].load(Ordering::Relaxed);
+
+        if self.period.is_zero() {
+            0.0
+        } else {
+            pulse_width as f64 / self.period.as_nanos() as f64
+        }
+    }
+
+    /// Sets the channel's duty cycle, which takes effect at the start of the next period.
+    ///
+    /// `duty_cycle` is specified as a floating point value between `0.0` (0%) and `1.0` (100%).
+    /// Returns [`Error::InvalidDutyCycle`] if `duty_cycle` isn't a finite value.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `channel` is out of range.
+    ///
+    /// [`Error::InvalidDutyCycle`]: enum.Error.html#variant.InvalidDutyCycle
+    pub fn set_duty_cycle(&self, channel: usize, duty_cycle: f64) -> Result<()> {
+        let pulse_width = duty_cycle_to_pulse_width(self.period, duty_cycle)?;
+
+        self.shared.pulse_widths[channel].store(pulse_width.as_nanos() as u64, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Returns the period error statistics, measured since the controller was
+    /// constructed or since the last call to [`reset_jitter`].
+    ///
+    /// [`reset_jitter`]: #method.reset_jitter
+    pub fn jitter(&self) -> JitterStats {
+        self.shared.jitter.lock().unwrap().stats()
+    }
+
+    /// Clears the period error statistics.
+    pub fn reset_jitter(&self) {
+        *self.shared.jitter.lock().unwrap() = JitterAccumulator::default();
+    }
+
+    /// Stops the timer thread, and sets all channels to low.
+    ///
+    /// The thread is stopped automatically when the `SoftPwmController` goes out of scope.
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop.take();
+
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| Error::ThreadPanic),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SoftPwmController {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::tests::simulated;
+    use crate::gpio::{Level, Mode};
+
+    #[test]
+    fn next_deadline_keeps_periods_back_to_back() {
+        let period = Duration::from_millis(100);
+        let now = Instant::now();
+
+        assert_eq!(next_deadline(now, period), now + period);
+        // Less than a period behind, so the thread can still catch up.
+        assert_eq!(next_deadline(now - period, period), now);
+
+        let restarted = next_deadline(now - period * 10, period);
+        assert!(restarted >= now);
+        assert!(restarted < now + period);
+    }
+
+    #[test]
+    fn soft_pwm_toggles_until_cleared() {
+        let (gpio, sim) = simulated();
+
+        let mut led = gpio.get(16).unwrap().into_output();
+        led.set_pwm_frequency(200.0, 0.5).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (mut seen_high, mut seen_low) = (false, false);
+        while !(seen_high && seen_low) {
+            assert!(Instant::now() < deadline, "pin never toggled");
+            match sim.level(16).unwrap() {
+                Level::High => seen_high = true,
+                Level::Low => seen_low = true,
+            }
+        }
+
+        led.set_pwm(Duration::from_millis(5), Duration::from_millis(10)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(sim.level(16).unwrap(), Level::High);
+
+        led.clear_pwm().unwrap();
+        assert_eq!(sim.level(16).unwrap(), Level::Low);
+
+        for &(frequency, duty_cycle) in [
+            (0.0, 0.5),
+            (-1.0, 0.5),
+            (f64::NAN, 0.5),
+            (f64::INFINITY, 0.5),
+            (1e-30, 0.5),
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                led.set_pwm_frequency(frequency, duty_cycle),
+                Err(Error::InvalidFrequency(_))
+            ));
+        }
+        assert!(matches!(
+            led.set_pwm_frequency(100.0, f64::NAN),
+            Err(Error::InvalidDutyCycle(_))
+        ));
+        assert_eq!(sim.level(16).unwrap(), Level::Low);
+
+        led.set_pwm(Duration::from_millis(5), Duration::from_millis(5)).unwrap();
+        drop(led);
+        assert_eq!(sim.mode(16).unwrap(), Mode::Input);
+        assert_eq!(sim.level(16).unwrap(), Level::Low);
+    }
+
+    #[test]
+    fn soft_pwm_controller_drives_all_channels() {
+        let (gpio, sim) = simulated();
+
+        let pins = [5, 6, 40]
+            .iter()
+            .map(|&pin| gpio.get(pin).unwrap().into_output())
+            .collect();
+        let mut leds = SoftPwmController::new(pins, Duration::from_millis(2)).unwrap();
+        leds.set_duty_cycle(1, 1.0).unwrap();
+        leds.set_duty_cycle(2, 0.5).unwrap();
+        assert!(matches!(
+            leds.set_duty_cycle(2, f64::NAN),
+            Err(Error::InvalidDutyCycle(_))
+        ));
+        assert_eq!(leds.duty_cycle(2), 0.5);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (mut seen_high, mut seen_low) = (false, false);
+        while !(seen_high && seen_low) || leds.jitter().periods < 10 {
+            assert!(Instant::now() < deadline, "channels never toggled");
+            match sim.level(40).unwrap() {
+                Level::High => seen_high = true,
+                Level::Low => seen_low = true,
+            }
+        }
+        assert_eq!(sim.level(5).unwrap(), Level::Low);
+        assert_eq!(sim.level(6).unwrap(), Level::High);
+
+        let jitter = leds.jitter();
+        assert!(jitter.min <= jitter.mean && jitter.mean <= jitter.max);
+        leds.reset_jitter();
+        assert_eq!(leds.jitter().periods, 0);
+
+        leds.stop().unwrap();
+        assert!([5, 6, 40].iter().all(|&pin| sim.level(pin).unwrap() == Level::Low));
+    }
+
+    #[test]
+    fn soft_pwm_controller_stops_mid_period() {
+        let (gpio, sim) = simulated();
+
+        let pins = vec![gpio.get(16).unwrap().into_output()];
+        let mut leds = SoftPwmController::new(pins, Duration::from_secs(60)).unwrap();
+        leds.set_duty_cycle(0, 0.5).unwrap();
+
+        let start = Instant::now();
+        leds.stop().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(sim.level(16).unwrap(), Level::Low);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod gpio;
+pub mod pwm;
+pub mod system;
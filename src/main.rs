@@ -1,13 +1,6 @@
-#[macro_use]
-pub mod macros;
-mod system;
-mod gpio;
+use mygpio::system::DeviceInfo;
+use mygpio::gpio::Gpio;
 
-use crate::system::DeviceInfo;
-use crate::gpio::mem::{GpioMem, PATH_DEV_GPIOMEM };
-use crate::gpio::pin::{OutputPin, Pin};
-
-use std::rc::Rc;
 use std::thread;
 use std::time::Duration;
 use std::sync::Arc;
@@ -23,21 +16,20 @@ fn main() {
 
     let dev_info = DeviceInfo::new();
     println!("{}", dev_info.unwrap());
-    if let Ok(gpio_mem) =GpioMem::open(){
-        println!("gpio_mem: {}", gpio_mem);
-        let gpio_rc = Rc::new(gpio_mem);
-        let mut out_pin3 = Pin::new(3,gpio_rc).into_output();
-        while  running.load(Ordering::SeqCst){
-            println!("led on");
-            out_pin3.set_high();
-            thread::sleep(Duration::from_millis(2000));
-            out_pin3.set_low();
-            println!("led off");
-            thread::sleep(Duration::from_millis(2000));
-        }
+    match Gpio::new().and_then(|gpio| gpio.get(3)) {
+        Ok(pin3) => {
+            let mut out_pin3 = pin3.into_output();
+            // Blink the led: 2 seconds on, 2 seconds off.
+            if let Err(err) = out_pin3.set_pwm(Duration::from_millis(4000), Duration::from_millis(2000)) {
+                println!("ERROR: {}", err);
+                return;
+            }
 
-    } else {
-        println!("ERROR: unable to map {:?}",PATH_DEV_GPIOMEM);
+            while  running.load(Ordering::SeqCst){
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        Err(err) => println!("ERROR: {}", err),
     }
 
 }
@@ -0,0 +1,558 @@
+mod sysfs;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::result;
+use std::time::Duration;
+
+use crate::gpio::{self, alt, Gpio};
+
+const PATH_SYSFS_PWM: &str = "/sys/class/pwm";
+// The BCM2835/BCM2711 PWM0 peripheral shows up as the first pwmchip.
+const CHIP: u8 = 0;
+// Pins that can be routed to PWM0 on the GPIO header.
+const PWM_PINS: [u8; 4] = [12, 13, 18, 19];
+
+/// Errors that can occur when accessing the PWM peripheral.
+#[derive(Debug)]
+pub enum Error {
+    /// I/O error.
+    Io(io::Error),
+    /// GPIO error.
+    ///
+    /// Invalid frequencies and duty cycles are reported as [`gpio::Error::InvalidFrequency`]
+    /// and [`gpio::Error::InvalidDutyCycle`], the same errors returned by the software PWM.
+    ///
+    /// [`gpio::Error::InvalidFrequency`]: ../gpio/enum.Error.html#variant.InvalidFrequency
+    /// [`gpio::Error::InvalidDutyCycle`]: ../gpio/enum.Error.html#variant.InvalidDutyCycle
+    Gpio(gpio::Error),
+    /// PWM channel isn't routed to any pin.
+    ///
+    /// None of the pins that can carry the channel's signal is set to the
+    /// appropriate alternate function mode. Channel 0 is available on BCM GPIO 12
+    /// ([`Alt0`]) and 18 ([`Alt5`]), channel 1 on BCM GPIO 13 ([`Alt0`]) and 19 ([`Alt5`]).
+    /// The mode is usually configured by the `pwm` or `pwm-2chan` overlay in
+    /// `/boot/config.txt`, or through [`Pin::into_alt`].
+    ///
+    /// [`Alt0`]: ../gpio/enum.Mode.html#variant.Alt0
+    /// [`Alt5`]: ../gpio/enum.Mode.html#variant.Alt5
+    /// [`Pin::into_alt`]: ../gpio/struct.Pin.html#method.into_alt
+    NotRouted(Channel),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "I/O error: {}", err),
+            Error::Gpio(ref err) => write!(f, "GPIO error: {}", err),
+            Error::NotRouted(channel) => write!(f, "{} is not routed to any pin", channel),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<gpio::Error> for Error {
+    fn from(err: gpio::Error) -> Error {
+        Error::Gpio(err)
+    }
+}
+
+/// Result type returned from methods that can have `pwm::Error`s.
+pub type Result<T> = result::Result<T, Error>;
+
+/// PWM channels.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum Channel {
+    Pwm0 = 0,
+    Pwm1 = 1,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Channel::Pwm0 => write!(f, "Pwm0"),
+            Channel::Pwm1 => write!(f, "Pwm1"),
+        }
+    }
+}
+
+impl Channel {
+    // The channel's function name in the BCM2835 and BCM2711 alternate function tables.
+    fn functions(self) -> [&'static str; 2] {
+        match self {
+            Channel::Pwm0 => ["PWM0", "PWM0_0"],
+            Channel::Pwm1 => ["PWM1", "PWM0_1"],
+        }
+    }
+}
+
+/// Output polarities.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Polarity {
+    Normal,
+    Inverse,
+}
+
+impl fmt::Display for Polarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Polarity::Normal => write!(f, "Normal"),
+            Polarity::Inverse => write!(f, "Inverse"),
+        }
+    }
+}
+
+// Returns the pin the channel is routed to, based on the pins' alternate function modes.
+fn routed_pin(gpio: &Gpio, channel: Channel) -> Result<u8> {
+    let gpio_mem = gpio.gpio_mem();
+
+    PWM_PINS
+        .iter()
+        .copied()
+        .find(|&pin| {
+            alt::function(gpio_mem.soc(), pin, gpio_mem.mode(pin))
+                .is_some_and(|function| channel.functions().contains(&function))
+        })
+        .ok_or(Error::NotRouted(channel))
+}
+
+/// Provides access to the Raspberry Pi's PWM peripheral.
+///
+/// The PWM peripheral is controlled through `/sys/class/pwm/pwmchip0`, which is
+/// only available after enabling it with the `pwm` or `pwm-2chan` overlay in
+/// `/boot/config.txt`. Unlike the software PWM available on [`OutputPin`], the
+/// signal is generated in hardware, so it's not affected by scheduling jitter.
+///
+/// By default, the channel is disabled and unexported when `Pwm` goes out of scope.
+///
+/// [`OutputPin`]: ../gpio/struct.OutputPin.html
+#[derive(Debug)]
+pub struct Pwm {
+    channel: Channel,
+    root: PathBuf,
+    reset_on_drop: bool,
+}
+
+impl Pwm {
+    /// Constructs a new `Pwm` using the specified frequency and duty cycle.
+    ///
+    /// `frequency` is specified in hertz (Hz).
+    ///
+    /// `duty_cycle` is specified as a floating point value between `0.0` (0%) and `1.0` (100%).
+    ///
+    /// Returns [`Error::NotRouted`] if none of the pins that can carry the channel's
+    /// signal is set to the appropriate alternate function mode in `gpio`, or
+    /// [`Error::Gpio`] if `frequency` isn't a finite value above `0.0`, or
+    /// `duty_cycle` isn't a finite value.
+    ///
+    /// [`Error::NotRouted`]: enum.Error.html#variant.NotRouted
+    /// [`Error::Gpio`]: enum.Error.html#variant.Gpio
+    pub fn with_frequency(
+        gpio: &Gpio,
+        channel: Channel,
+        frequency: f64,
+        duty_cycle: f64,
+        polarity: Polarity,
+        enabled: bool,
+    ) -> Result<Pwm> {
+        let (period, pulse_width) = gpio::frequency_to_period(frequency, duty_cycle)?;
+
+        Pwm::with_root(
+            PATH_SYSFS_PWM,
+            Some(gpio),
+            channel,
+            period,
+            pulse_width,
+            polarity,
+            enabled,
+        )
+    }
+
+    /// Constructs a new `Pwm` using the specified period and pulse width.
+    ///
+    /// `pulse_width` indicates the amount of time the PWM signal is active during a
+    /// single period, and is capped at `period`.
+    ///
+    /// Returns [`Error::NotRouted`] if none of the pins that can carry the channel's
+    /// signal is set to the appropriate alternate function mode in `gpio`.
+    ///
+    /// [`Error::NotRouted`]: enum.Error.html#variant.NotRouted
+    pub fn with_period(
+        gpio: &Gpio,
+        channel: Channel,
+        period: Duration,
+        pulse_width: Duration,
+        polarity: Polarity,
+        enabled: bool,
+    ) -> Result<Pwm> {
+        Pwm::with_root(
+            PATH_SYSFS_PWM,
+            Some(gpio),
+            channel,
+            period,
+            pulse_width,
+            polarity,
+            enabled,
+        )
+    }
+
+    /// Constructs a new `Pwm` using the specified period and pulse width, accessing
+    /// the pwmchip under `root` instead of `/sys/class/pwm`.
+    ///
+    /// The routing check is skipped if `gpio` is `None`, for instance on models
+    /// where [`Gpio`] isn't supported.
+    ///
+    /// Returns [`Error::NotRouted`] if `gpio` is provided, and none of the pins that can
+    /// carry the channel's signal is set to the appropriate alternate function mode.
+    ///
+    /// [`Gpio`]: ../gpio/struct.Gpio.html
+    /// [`Error::NotRouted`]: enum.Error.html#variant.NotRouted
+    pub fn with_root<P: AsRef<Path>>(
+        root: P,
+        gpio: Option<&Gpio>,
+        channel: Channel,
+        period: Duration,
+        pulse_width: Duration,
+        polarity: Polarity,
+        enabled: bool,
+    ) -> Result<Pwm> {
+        if let Some(gpio) = gpio {
+            routed_pin(gpio, channel)?;
+        }
+
+        let root = root.as_ref().to_path_buf();
+        sysfs::export(&root, CHIP, channel as u8)?;
+
+        let pwm = Pwm {
+            channel,
+            root,
+            reset_on_drop: true,
+        };
+
+        // The polarity can only be changed while the channel is disabled.
+        pwm.disable()?;
+        pwm.set_period_and_pulse_width(period, pulse_width.min(period))?;
+        pwm.set_polarity(polarity)?;
+        if enabled {
+            pwm.enable()?;
+        }
+
+        Ok(pwm)
+    }
+
+    /// Returns the PWM channel.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Returns the period.
+    pub fn period(&self) -> Result<Duration> {
+        Ok(Duration::from_nanos(sysfs::period(
+            &self.root,
+            CHIP,
+            self.channel as u8,
+        )?))
+    }
+
+    /// Sets the period.
+    ///
+    /// If the current pulse width is longer than `period`, it's shortened to `period`.
+    pub fn set_period(&self, period: Duration) -> Result<()> {
+        let pulse_width = self.pulse_width()?.min(period);
+
+        self.set_period_and_pulse_width(period, pulse_width)
+    }
+
+    /// Returns the pulse width.
+    pub fn pulse_width(&self) -> Result<Duration> {
+        Ok(Duration::from_nanos(sysfs::pulse_width(
+            &self.root,
+            CHIP,
+            self.channel as u8,
+        )?))
+    }
+
+    /// Sets the pulse width.
+    ///
+    /// `pulse_width` indicates the amount of time the PWM signal is active during a
+    /// single period, and can't be longer than the period.
+    pub fn set_pulse_width(&self, pulse_width: Duration) -> Result<()> {
+        sysfs::set_pulse_width(&self.root, CHIP, self.channel as u8, nanos(pulse_width))?;
+
+        Ok(())
+    }
+
+    /// Returns the frequency in hertz (Hz).
+    pub fn frequency(&self) -> Result<f64> {
+        let period = self.period()?;
+
+        Ok(if period.is_zero() {
+            0.0
+        } else {
+            1.0 / period.as_secs_f64()
+        })
+    }
+
+    /// Returns the duty cycle as a floating point value between `0.0` (0%) and `1.0` (100%).
+    pub fn duty_cycle(&self) -> Result<f64> {
+        let period = self.period()?;
+
+        Ok(if period.is_zero() {
+            0.0
+        } else {
+            self.pulse_width()?.as_secs_f64() / period.as_secs_f64()
+        })
+    }
+
+    /// Sets the frequency and duty cycle.
+    ///
+    /// `frequency` is specified in hertz (Hz).
+    ///
+    /// `duty_cycle` is specified as a floating point value between `0.0` (0%) and `1.0` (100%).
+    ///
+    /// Returns [`Error::Gpio`] if `frequency` isn't a finite value above `0.0`, or
+    /// `duty_cycle` isn't a finite value.
+    ///
+    /// [`Error::Gpio`]: enum.Error.html#variant.Gpio
+    pub fn set_frequency(&self, frequency: f64, duty_cycle: f64) -> Result<()> {
+        let (period, pulse_width) = gpio::frequency_to_period(frequency, duty_cycle)?;
+
+        self.set_period_and_pulse_width(period, pulse_width)
+    }
+
+    /// Returns the polarity.
+    pub fn polarity(&self) -> Result<Polarity> {
+        Ok(sysfs::polarity(&self.root, CHIP, self.channel as u8)?)
+    }
+
+    /// Sets the polarity.
+    ///
+    /// Depending on the driver, the polarity can only be changed while the channel
+    /// is disabled.
+    pub fn set_polarity(&self, polarity: Polarity) -> Result<()> {
+        sysfs::set_polarity(&self.root, CHIP, self.channel as u8, polarity)?;
+
+        Ok(())
+    }
+
+    /// Returns `true` if the PWM channel is enabled.
+    pub fn is_enabled(&self) -> Result<bool> {
+        Ok(sysfs::enabled(&self.root, CHIP, self.channel as u8)?)
+    }
+
+    /// Enables the PWM channel.
+    pub fn enable(&self) -> Result<()> {
+        sysfs::set_enabled(&self.root, CHIP, self.channel as u8, true)?;
+
+        Ok(())
+    }
+
+    /// Disables the PWM channel.
+    pub fn disable(&self) -> Result<()> {
+        sysfs::set_enabled(&self.root, CHIP, self.channel as u8, false)?;
+
+        Ok(())
+    }
+
+    /// Returns the value of `reset_on_drop`.
+    pub fn reset_on_drop(&self) -> bool {
+        self.reset_on_drop
+    }
+
+    /// When enabled, disables and unexports the PWM channel when the `Pwm` instance
+    /// goes out of scope. By default, this is set to `true`.
+    pub fn set_reset_on_drop(&mut self, reset_on_drop: bool) {
+        self.reset_on_drop = reset_on_drop;
+    }
+
+    // The kernel rejects a pulse width that's longer than the period, so the
+    // order of the writes depends on the current period.
+    fn set_period_and_pulse_width(&self, period: Duration, pulse_width: Duration) -> Result<()> {
+        let channel = self.channel as u8;
+
+        if pulse_width > self.period()? {
+            sysfs::set_period(&self.root, CHIP, channel, nanos(period))?;
+            sysfs::set_pulse_width(&self.root, CHIP, channel, nanos(pulse_width))?;
+        } else {
+            sysfs::set_pulse_width(&self.root, CHIP, channel, nanos(pulse_width))?;
+            sysfs::set_period(&self.root, CHIP, channel, nanos(period))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Pwm {
+    fn drop(&mut self) {
+        if self.reset_on_drop {
+            let _ = self.disable();
+            let _ = sysfs::unexport(&self.root, CHIP, self.channel as u8);
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::gpio::{Mode, SimulatedGpio};
+    use crate::system::SoC;
+
+    // Temporary copy of /sys/class/pwm with pwm0 already exported, since there's
+    // no kernel to create the channel directory.
+    struct FakeSysfs {
+        root: PathBuf,
+    }
+
+    impl FakeSysfs {
+        fn new(name: &str) -> FakeSysfs {
+            let root = env::temp_dir().join(format!("mygpio-pwm-{}-{}", process::id(), name));
+            let channel = root.join("pwmchip0/pwm0");
+            fs::create_dir_all(&channel).unwrap();
+
+            for &(file, value) in [("export", ""), ("unexport", "")].iter() {
+                fs::write(root.join("pwmchip0").join(file), value).unwrap();
+            }
+            for &(file, value) in [
+                ("period", "0"),
+                ("duty_cycle", "0"),
+                ("polarity", "normal"),
+                ("enable", "0"),
+            ]
+            .iter()
+            {
+                fs::write(channel.join(file), value).unwrap();
+            }
+
+            FakeSysfs { root }
+        }
+
+        fn read(&self, file: &str) -> String {
+            fs::read_to_string(self.root.join("pwmchip0/pwm0").join(file)).unwrap()
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn configures_channel_through_sysfs() {
+        let sysfs = FakeSysfs::new("configure");
+        let (period, pulse_width) = gpio::frequency_to_period(50.0, 0.075).unwrap();
+
+        let pwm = Pwm::with_root(
+            &sysfs.root,
+            None,
+            Channel::Pwm0,
+            period,
+            pulse_width,
+            Polarity::Inverse,
+            true,
+        )
+        .unwrap();
+        assert_eq!(sysfs.read("period"), "20000000");
+        assert_eq!(sysfs.read("duty_cycle"), "1500000");
+        assert_eq!(sysfs.read("polarity"), "inversed");
+        assert_eq!(sysfs.read("enable"), "1");
+        assert_eq!(pwm.frequency().unwrap(), 50.0);
+        assert!((pwm.duty_cycle().unwrap() - 0.075).abs() < 1e-9);
+
+        pwm.set_frequency(1000.0, 0.25).unwrap();
+        assert_eq!(pwm.period().unwrap(), Duration::from_millis(1));
+        assert_eq!(pwm.pulse_width().unwrap(), Duration::from_micros(250));
+
+        for &(frequency, duty_cycle) in [(0.0, 0.5), (f64::NAN, 0.5), (1e-30, 0.5)].iter() {
+            assert!(matches!(
+                pwm.set_frequency(frequency, duty_cycle),
+                Err(Error::Gpio(gpio::Error::InvalidFrequency(_)))
+            ));
+        }
+        assert!(matches!(
+            pwm.set_frequency(1000.0, f64::NAN),
+            Err(Error::Gpio(gpio::Error::InvalidDutyCycle(_)))
+        ));
+        assert_eq!(pwm.period().unwrap(), Duration::from_millis(1));
+
+        pwm.set_period(Duration::from_micros(100)).unwrap();
+        assert_eq!(pwm.pulse_width().unwrap(), Duration::from_micros(100));
+
+        drop(pwm);
+        assert_eq!(sysfs.read("enable"), "0");
+        assert_eq!(
+            fs::read_to_string(sysfs.root.join("pwmchip0/unexport")).unwrap(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn routing_follows_alt_function_mode() {
+        for &(soc, mode, pin) in [
+            (SoC::Bcm2711, Mode::Alt5, 18),
+            (SoC::Bcm2837, Mode::Alt0, 13),
+        ]
+        .iter()
+        {
+            let gpio = Gpio::with_backend(SimulatedGpio::new(), soc);
+            let channel = if pin == 18 {
+                Channel::Pwm0
+            } else {
+                Channel::Pwm1
+            };
+            assert!(matches!(
+                routed_pin(&gpio, channel),
+                Err(Error::NotRouted(_))
+            ));
+
+            let _pwm_pin = gpio.get(pin).unwrap().into_alt(mode).unwrap();
+            assert_eq!(routed_pin(&gpio, channel).unwrap(), pin, "{}", soc);
+        }
+    }
+
+    #[test]
+    fn constructor_checks_routing_before_exporting() {
+        let sysfs = FakeSysfs::new("routing");
+        let gpio = Gpio::with_backend(SimulatedGpio::new(), SoC::Bcm2711);
+        let new_pwm = |gpio| {
+            Pwm::with_root(
+                &sysfs.root,
+                gpio,
+                Channel::Pwm0,
+                Duration::from_millis(20),
+                Duration::from_millis(1),
+                Polarity::Normal,
+                true,
+            )
+        };
+
+        assert!(matches!(
+            new_pwm(Some(&gpio)),
+            Err(Error::NotRouted(Channel::Pwm0))
+        ));
+        assert_eq!(sysfs.read("enable"), "0");
+
+        let _pwm_pin = gpio.get(12).unwrap().into_alt(Mode::Alt0).unwrap();
+        let pwm = new_pwm(Some(&gpio)).unwrap();
+        assert_eq!(pwm.period().unwrap(), Duration::from_millis(20));
+        assert_eq!(sysfs.read("enable"), "1");
+    }
+}
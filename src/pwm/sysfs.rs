@@ -0,0 +1,143 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::pwm::Polarity;
+
+// After exporting a channel, udev needs some time to create the channel's
+// directory and set its permissions.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(1);
+const EXPORT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn chip_path(root: &Path, chip: u8) -> PathBuf {
+    root.join(format!("pwmchip{}", chip))
+}
+
+fn channel_path(root: &Path, chip: u8, channel: u8) -> PathBuf {
+    chip_path(root, chip).join(format!("pwm{}", channel))
+}
+
+fn read_value(path: &Path) -> io::Result<String> {
+    let mut value = String::new();
+    File::open(path)?.read_to_string(&mut value)?;
+
+    Ok(value.trim().to_owned())
+}
+
+fn read_u64(path: &Path) -> io::Result<u64> {
+    read_value(path)?
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{}", path.display())))
+}
+
+fn write_value(path: &Path, value: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)?
+        .write_all(value.as_bytes())
+}
+
+/// Exports the channel, and waits until its attributes are writable.
+pub(crate) fn export(root: &Path, chip: u8, channel: u8) -> io::Result<()> {
+    let channel_path = channel_path(root, chip, channel);
+
+    if !channel_path.exists() {
+        write_value(&chip_path(root, chip).join("export"), &channel.to_string())?;
+    }
+
+    let enable = channel_path.join("enable");
+    let mut waited = Duration::ZERO;
+    loop {
+        match OpenOptions::new().write(true).open(&enable) {
+            Ok(_) => return Ok(()),
+            Err(err) if waited >= EXPORT_TIMEOUT => return Err(err),
+            Err(err)
+                if err.kind() == io::ErrorKind::NotFound
+                    || err.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                thread::sleep(EXPORT_POLL_INTERVAL);
+                waited += EXPORT_POLL_INTERVAL;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+pub(crate) fn unexport(root: &Path, chip: u8, channel: u8) -> io::Result<()> {
+    if fs::metadata(channel_path(root, chip, channel)).is_ok() {
+        write_value(
+            &chip_path(root, chip).join("unexport"),
+            &channel.to_string(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns the period in nanoseconds.
+pub(crate) fn period(root: &Path, chip: u8, channel: u8) -> io::Result<u64> {
+    read_u64(&channel_path(root, chip, channel).join("period"))
+}
+
+pub(crate) fn set_period(root: &Path, chip: u8, channel: u8, period: u64) -> io::Result<()> {
+    write_value(
+        &channel_path(root, chip, channel).join("period"),
+        &period.to_string(),
+    )
+}
+
+/// Returns the pulse width in nanoseconds.
+pub(crate) fn pulse_width(root: &Path, chip: u8, channel: u8) -> io::Result<u64> {
+    read_u64(&channel_path(root, chip, channel).join("duty_cycle"))
+}
+
+pub(crate) fn set_pulse_width(
+    root: &Path,
+    chip: u8,
+    channel: u8,
+    pulse_width: u64,
+) -> io::Result<()> {
+    write_value(
+        &channel_path(root, chip, channel).join("duty_cycle"),
+        &pulse_width.to_string(),
+    )
+}
+
+pub(crate) fn polarity(root: &Path, chip: u8, channel: u8) -> io::Result<Polarity> {
+    match read_value(&channel_path(root, chip, channel).join("polarity"))?.as_str() {
+        "normal" => Ok(Polarity::Normal),
+        "inversed" => Ok(Polarity::Inverse),
+        value => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown polarity: {}", value),
+        )),
+    }
+}
+
+pub(crate) fn set_polarity(
+    root: &Path,
+    chip: u8,
+    channel: u8,
+    polarity: Polarity,
+) -> io::Result<()> {
+    let value = match polarity {
+        Polarity::Normal => "normal",
+        Polarity::Inverse => "inversed",
+    };
+
+    write_value(&channel_path(root, chip, channel).join("polarity"), value)
+}
+
+pub(crate) fn enabled(root: &Path, chip: u8, channel: u8) -> io::Result<bool> {
+    Ok(read_value(&channel_path(root, chip, channel).join("enable"))? == "1")
+}
+
+pub(crate) fn set_enabled(root: &Path, chip: u8, channel: u8, enabled: bool) -> io::Result<()> {
+    write_value(
+        &channel_path(root, chip, channel).join("enable"),
+        if enabled { "1" } else { "0" },
+    )
+}
@@ -1,12 +1,22 @@
 use std::error;
 use std::fmt;
 use std::fs;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::result;
 
-const PERIPHERAL_BASE_RPI4: u32 = 0xfe00_0000;
-const GPIO_OFFSET: u32 = 0x20_0000;
+const PATH_PROC_CPUINFO: &str = "/proc/cpuinfo";
+const PATH_DT_COMPATIBLE: &str = "/sys/firmware/devicetree/base/compatible";
+const PATH_DT_MODEL: &str = "/sys/firmware/devicetree/base/model";
+const PATH_DT_RANGES: &str = "/proc/device-tree/soc/ranges";
+
+// Physical addresses of the peripherals, as seen by the ARM cores.
+const PERIPHERAL_BASE_RPI: u64 = 0x2000_0000;
+const PERIPHERAL_BASE_RPI2: u64 = 0x3f00_0000;
+const PERIPHERAL_BASE_RPI4: u64 = 0xfe00_0000;
+// On the Pi 5, GPIO is handled by the RP1 I/O controller rather than the SoC.
+const PERIPHERAL_BASE_RP1: u64 = 0x1f_0000_0000;
+const GPIO_OFFSET: u64 = 0x20_0000;
+const GPIO_OFFSET_RP1: u64 = 0xd_0000;
 
 /// Errors that can occur when trying to identify the Raspberry Pi hardware.
 #[derive(Debug)]
@@ -41,19 +51,91 @@ impl error::Error for Error {}
 pub type Result<T> = result::Result<T, Error>;
 
 
+/// Identifiable Raspberry Pi models.
+///
+/// `Model` might be extended with additional variants in a minor or
+/// patch revision, and must not be exhaustively matched against.
+/// Instead, add a `_` catch-all arm to match future variants.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Model {
+    RaspberryPiModelA,
+    RaspberryPiModelAPlus,
+    RaspberryPiModelBRev1,
+    RaspberryPiModelBRev2,
+    RaspberryPiModelBPlus,
+    RaspberryPiComputeModule,
+    RaspberryPi2ModelB,
+    RaspberryPi3ModelB,
+    RaspberryPi3ModelBPlus,
+    RaspberryPi3ModelAPlus,
+    RaspberryPiComputeModule3,
+    RaspberryPiComputeModule3Plus,
     RaspberryPi4ModelB,
+    RaspberryPi400,
+    RaspberryPiComputeModule4,
+    RaspberryPiComputeModule4S,
+    RaspberryPiZero,
+    RaspberryPiZeroW,
+    RaspberryPiZero2W,
+    RaspberryPi5,
+    RaspberryPi500,
+    RaspberryPiComputeModule5,
+    RaspberryPiComputeModule5Lite,
 }
 
 impl fmt::Display for Model {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
+            Model::RaspberryPiModelA => write!(f, "RaspberryPiModelA"),
+            Model::RaspberryPiModelAPlus => write!(f, "RaspberryPiModelAPlus"),
+            Model::RaspberryPiModelBRev1 => write!(f, "RaspberryPiModelBRev1"),
+            Model::RaspberryPiModelBRev2 => write!(f, "RaspberryPiModelBRev2"),
+            Model::RaspberryPiModelBPlus => write!(f, "RaspberryPiModelBPlus"),
+            Model::RaspberryPiComputeModule => write!(f, "RaspberryPiComputeModule"),
+            Model::RaspberryPi2ModelB => write!(f, "RaspberryPi2ModelB"),
+            Model::RaspberryPi3ModelB => write!(f, "RaspberryPi3ModelB"),
+            Model::RaspberryPi3ModelBPlus => write!(f, "RaspberryPi3ModelBPlus"),
+            Model::RaspberryPi3ModelAPlus => write!(f, "RaspberryPi3ModelAPlus"),
+            Model::RaspberryPiComputeModule3 => write!(f, "RaspberryPiComputeModule3"),
+            Model::RaspberryPiComputeModule3Plus => write!(f, "RaspberryPiComputeModule3Plus"),
             Model::RaspberryPi4ModelB => write!(f, "RaspberryPi4ModelB"),
+            Model::RaspberryPi400 => write!(f, "RaspberryPi400"),
+            Model::RaspberryPiComputeModule4 => write!(f, "RaspberryPiComputeModule4"),
+            Model::RaspberryPiComputeModule4S => write!(f, "RaspberryPiComputeModule4S"),
+            Model::RaspberryPiZero => write!(f, "RaspberryPiZero"),
+            Model::RaspberryPiZeroW => write!(f, "RaspberryPiZeroW"),
+            Model::RaspberryPiZero2W => write!(f, "RaspberryPiZero2W"),
+            Model::RaspberryPi5 => write!(f, "RaspberryPi5"),
+            Model::RaspberryPi500 => write!(f, "RaspberryPi500"),
+            Model::RaspberryPiComputeModule5 => write!(f, "RaspberryPiComputeModule5"),
+            Model::RaspberryPiComputeModule5Lite => write!(f, "RaspberryPiComputeModule5Lite"),
         }
     }
 }
 
+/// Board manufacturers, as encoded in the revision code.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Manufacturer {
+    SonyUk,
+    SonyJapan,
+    Egoman,
+    Embest,
+    Qisda,
+    Stadium,
+}
+
+impl fmt::Display for Manufacturer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Manufacturer::SonyUk => write!(f, "Sony UK"),
+            Manufacturer::SonyJapan => write!(f, "Sony Japan"),
+            Manufacturer::Egoman => write!(f, "Egoman"),
+            Manufacturer::Embest => write!(f, "Embest"),
+            Manufacturer::Qisda => write!(f, "Qisda"),
+            Manufacturer::Stadium => write!(f, "Stadium"),
+        }
+    }
+}
 
 /// Identifiable Raspberry Pi SoCs.
 ///
@@ -62,72 +144,787 @@ impl fmt::Display for Model {
 /// Instead, add a `_` catch-all arm to match future variants.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum SoC {
+    Bcm2835,
+    Bcm2836,
+    Bcm2837,
     Bcm2711,
+    Bcm2712,
 }
 
 impl fmt::Display for SoC {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
+            SoC::Bcm2835 => write!(f, "BCM2835"),
+            SoC::Bcm2836 => write!(f, "BCM2836"),
+            SoC::Bcm2837 => write!(f, "BCM2837"),
             SoC::Bcm2711 => write!(f, "BCM2711"),
+            SoC::Bcm2712 => write!(f, "BCM2712"),
+        }
+    }
+}
+
+// Fields decoded from the revision code in /proc/cpuinfo.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct Revision {
+    model: Model,
+    soc: SoC,
+    memory: u32,
+    manufacturer: Manufacturer,
+    board_revision: u8,
+}
+
+impl Revision {
+    /// Decodes a revision code.
+    ///
+    /// New-style codes (bit 23 set) are bit fields laid out as
+    /// `NOQu_uuWu_FMMM_CCCC_PPPP_TTTT_TTTT_RRRR`: memory size (M), manufacturer (C),
+    /// processor (P), board type (T) and board revision (R). Old-style codes are
+    /// only used by the original Pi 1 boards, and are looked up in a table.
+    fn decode(code: u32) -> Result<Revision> {
+        if code & (1 << 23) == 0 {
+            return Revision::decode_old_style(code);
         }
+
+        let model = match (code >> 4) & 0xff {
+            0x00 => Model::RaspberryPiModelA,
+            0x01 => Model::RaspberryPiModelBRev2,
+            0x02 => Model::RaspberryPiModelAPlus,
+            0x03 => Model::RaspberryPiModelBPlus,
+            0x04 => Model::RaspberryPi2ModelB,
+            0x06 => Model::RaspberryPiComputeModule,
+            0x08 => Model::RaspberryPi3ModelB,
+            0x09 => Model::RaspberryPiZero,
+            0x0a => Model::RaspberryPiComputeModule3,
+            0x0c => Model::RaspberryPiZeroW,
+            0x0d => Model::RaspberryPi3ModelBPlus,
+            0x0e => Model::RaspberryPi3ModelAPlus,
+            0x10 => Model::RaspberryPiComputeModule3Plus,
+            0x11 => Model::RaspberryPi4ModelB,
+            0x12 => Model::RaspberryPiZero2W,
+            0x13 => Model::RaspberryPi400,
+            0x14 => Model::RaspberryPiComputeModule4,
+            0x15 => Model::RaspberryPiComputeModule4S,
+            0x17 => Model::RaspberryPi5,
+            0x18 => Model::RaspberryPiComputeModule5,
+            0x19 => Model::RaspberryPi500,
+            0x1a => Model::RaspberryPiComputeModule5Lite,
+            _ => return Err(Error::UnknownModel),
+        };
+
+        let soc = match (code >> 12) & 0xf {
+            0 => SoC::Bcm2835,
+            1 => SoC::Bcm2836,
+            2 => SoC::Bcm2837,
+            3 => SoC::Bcm2711,
+            4 => SoC::Bcm2712,
+            _ => return Err(Error::UnknownModel),
+        };
+
+        let manufacturer = match (code >> 16) & 0xf {
+            0 => Manufacturer::SonyUk,
+            1 => Manufacturer::Egoman,
+            2 | 4 => Manufacturer::Embest,
+            3 => Manufacturer::SonyJapan,
+            5 => Manufacturer::Stadium,
+            _ => return Err(Error::UnknownModel),
+        };
+
+        let memory = match (code >> 20) & 0b111 {
+            size @ 0..=6 => 256 << size,
+            _ => return Err(Error::UnknownModel),
+        };
+
+        Ok(Revision {
+            model,
+            soc,
+            memory,
+            manufacturer,
+            board_revision: (code & 0xf) as u8,
+        })
+    }
+
+    fn decode_old_style(code: u32) -> Result<Revision> {
+        use self::Manufacturer::*;
+        use self::Model::*;
+
+        // Bit 24 is set on boards that have been overvolted.
+        let (model, memory, manufacturer) = match code & 0x00ff_ffff {
+            0x02 | 0x03 => (RaspberryPiModelBRev1, 256, Egoman),
+            0x04 => (RaspberryPiModelBRev2, 256, SonyUk),
+            0x05 => (RaspberryPiModelBRev2, 256, Qisda),
+            0x06 => (RaspberryPiModelBRev2, 256, Egoman),
+            0x07 => (RaspberryPiModelA, 256, Egoman),
+            0x08 => (RaspberryPiModelA, 256, SonyUk),
+            0x09 => (RaspberryPiModelA, 256, Qisda),
+            0x0d => (RaspberryPiModelBRev2, 512, Egoman),
+            0x0e => (RaspberryPiModelBRev2, 512, SonyUk),
+            0x0f => (RaspberryPiModelBRev2, 512, Egoman),
+            0x10 => (RaspberryPiModelBPlus, 512, SonyUk),
+            0x11 => (RaspberryPiComputeModule, 512, SonyUk),
+            0x12 => (RaspberryPiModelAPlus, 256, SonyUk),
+            0x13 => (RaspberryPiModelBPlus, 512, Embest),
+            0x14 => (RaspberryPiComputeModule, 512, Embest),
+            0x15 => (RaspberryPiModelAPlus, 512, Embest),
+            _ => return Err(Error::UnknownModel),
+        };
+
+        Ok(Revision {
+            model,
+            soc: SoC::Bcm2835,
+            memory,
+            manufacturer,
+            board_revision: 0,
+        })
     }
 }
 
+/// Where a [`DeviceInfo`] field was determined from.
+///
+/// Useful to diagnose misdetection on distributions that don't provide
+/// all of the usual system files. `Source` displays as the file's usual
+/// location. [`DeviceInfo::source_path`] returns the file that was actually
+/// read, which differs for a `DeviceInfo` created with [`DeviceInfo::with_root`].
+///
+/// [`DeviceInfo`]: struct.DeviceInfo.html
+/// [`DeviceInfo::source_path`]: struct.DeviceInfo.html#method.source_path
+/// [`DeviceInfo::with_root`]: struct.DeviceInfo.html#method.with_root
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Source {
+    /// The `Revision` line in `/proc/cpuinfo`.
+    CpuInfo,
+    /// `/sys/firmware/devicetree/base/compatible`.
+    DeviceTreeCompatible,
+    /// `/sys/firmware/devicetree/base/model`.
+    DeviceTreeModel,
+    /// `/proc/device-tree/soc/ranges`.
+    DeviceTreeRanges,
+    /// Derived from the detected model or SoC.
+    Default,
+}
+
+impl Source {
+    // Returns the file's usual location, or `None` for `Default`.
+    fn path(self) -> Option<&'static str> {
+        match self {
+            Source::CpuInfo => Some(PATH_PROC_CPUINFO),
+            Source::DeviceTreeCompatible => Some(PATH_DT_COMPATIBLE),
+            Source::DeviceTreeModel => Some(PATH_DT_MODEL),
+            Source::DeviceTreeRanges => Some(PATH_DT_RANGES),
+            Source::Default => None,
+        }
+    }
+}
+
+// Returns `absolute` relocated under `root`. Path::join would discard the root,
+// as the system file paths are absolute.
+fn join_root(root: &Path, absolute: &str) -> PathBuf {
+    root.join(absolute.trim_start_matches('/'))
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path().unwrap_or("default"))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DeviceInfo {
     model: Model,
     soc: SoC,
-    peripheral_base: u32,
-    gpio_offset: u32,
+    memory: Option<u32>,
+    manufacturer: Option<Manufacturer>,
+    board_revision: Option<u8>,
+    peripheral_base: u64,
+    gpio_offset: u64,
+    model_source: Source,
+    soc_source: Source,
+    peripheral_base_source: Source,
+    // Directory the system files were read from, if any.
+    root: Option<PathBuf>,
 }
 
 impl fmt::Display for DeviceInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "(model:{:?} soc: {:?} peripheral_base: {:#02X} gpio_offset: {:#02X})",
-               self.model, self.soc, self.peripheral_base, self.gpio_offset)
+        let source = |source: Source| match self.source_path(source) {
+            Some(path) => path.display().to_string(),
+            None => source.to_string(),
+        };
+
+        write!(f, "(model:{:?} [{}] soc: {:?} [{}] peripheral_base: {:#02X} [{}] gpio_offset: {:#02X}",
+               self.model, source(self.model_source), self.soc, source(self.soc_source),
+               self.peripheral_base, source(self.peripheral_base_source), self.gpio_offset)?;
+        if let Some(memory) = self.memory {
+            write!(f, " memory: {}MB", memory)?;
+        }
+        if let Some(manufacturer) = self.manufacturer {
+            write!(f, " manufacturer: {}", manufacturer)?;
+        }
+        write!(f, ")")
     }
 }
 
-impl DeviceInfo {
-    fn parse_proc_cpuinfo() -> Result<DeviceInfo> {
-        let proc_file = File::open("/proc/cpuinfo");
-        let proc_cpuinfo = BufReader::new(proc_file.unwrap());
-        let mut hardware: String = String::new();
-        let mut revision: String = String::new();
-        for line_result in proc_cpuinfo.lines() {
-            if let Ok(line) = line_result {
-                if let Some(line_value) = line.strip_prefix("Hardware\t: ") {
-                    hardware = String::from(line_value);
-                } else if let Some(line_value) = line.strip_prefix("Revision\t: ") {
-                    revision = String::from(line_value).to_lowercase();
-                }
+// Returns the revision code from /proc/cpuinfo, decoded.
+fn parse_proc_cpuinfo(cpuinfo: &str) -> Option<Revision> {
+    let mut hardware = None;
+    let mut revision = None;
+    for line in cpuinfo.lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            match key.trim() {
+                "Hardware" => hardware = Some(value.trim()),
+                "Revision" => revision = Some(value.trim()),
+                _ => {}
             }
         }
-        match &hardware[..] {
-            "BCM2711" => {}
-            _ => return Err(Error::UnknownModel),
+    }
+
+    // Depending on the kernel, the Hardware line reports the actual SoC or
+    // one of the older BCM27xx/BCM283x names, so the SoC is taken from the
+    // revision code instead. 64-bit kernels leave out the Hardware line entirely.
+    if let Some(hardware) = hardware {
+        if !hardware.starts_with("BCM") {
+            return None;
         }
-        let model = {
-            match &revision[..] {
-                "d03114" => Model::RaspberryPi4ModelB,
-                _ => return Err(Error::UnknownModel),
+    }
+
+    let code = u32::from_str_radix(revision?, 16).ok()?;
+    Revision::decode(code).ok()
+}
+
+// Device-tree properties are NUL-terminated, and `compatible` holds several
+// NUL-separated strings, most specific first.
+fn dt_strings(contents: &[u8]) -> impl Iterator<Item = &str> {
+    contents
+        .split(|&b| b == 0)
+        .filter_map(|s| std::str::from_utf8(s).ok())
+        .filter(|s| !s.is_empty())
+}
+
+// Returns the model from the device tree's compatible property, and the SoC
+// if it's listed as well.
+fn parse_dt_compatible(compatible: &[u8]) -> Option<(Model, Option<SoC>)> {
+    let mut model = None;
+    let mut soc = None;
+    for entry in dt_strings(compatible) {
+        match entry {
+            "raspberrypi,model-a" => model = Some(Model::RaspberryPiModelA),
+            "raspberrypi,model-a-plus" => model = Some(Model::RaspberryPiModelAPlus),
+            "raspberrypi,model-b" | "raspberrypi,model-b-i2c0" => {
+                model = Some(Model::RaspberryPiModelBRev1)
             }
-        };
-        Ok(
-            {
-                DeviceInfo {
-                    model: model,
-                    soc: SoC::Bcm2711,
-                    peripheral_base: PERIPHERAL_BASE_RPI4,
-                    gpio_offset: GPIO_OFFSET,
-                }
+            "raspberrypi,model-b-rev2" => model = Some(Model::RaspberryPiModelBRev2),
+            "raspberrypi,model-b-plus" => model = Some(Model::RaspberryPiModelBPlus),
+            "raspberrypi,compute-module" => model = Some(Model::RaspberryPiComputeModule),
+            "raspberrypi,2-model-b" => model = Some(Model::RaspberryPi2ModelB),
+            "raspberrypi,3-model-b" => model = Some(Model::RaspberryPi3ModelB),
+            "raspberrypi,3-model-b-plus" => model = Some(Model::RaspberryPi3ModelBPlus),
+            "raspberrypi,3-model-a-plus" => model = Some(Model::RaspberryPi3ModelAPlus),
+            "raspberrypi,3-compute-module" => model = Some(Model::RaspberryPiComputeModule3),
+            "raspberrypi,3-compute-module-plus" => {
+                model = Some(Model::RaspberryPiComputeModule3Plus)
             }
-        )
+            "raspberrypi,4-model-b" => model = Some(Model::RaspberryPi4ModelB),
+            "raspberrypi,400" => model = Some(Model::RaspberryPi400),
+            "raspberrypi,4-compute-module" => model = Some(Model::RaspberryPiComputeModule4),
+            "raspberrypi,4-compute-module-s" => model = Some(Model::RaspberryPiComputeModule4S),
+            "raspberrypi,model-zero" => model = Some(Model::RaspberryPiZero),
+            "raspberrypi,model-zero-w" => model = Some(Model::RaspberryPiZeroW),
+            "raspberrypi,model-zero-2-w" => model = Some(Model::RaspberryPiZero2W),
+            "raspberrypi,5-model-b" => model = Some(Model::RaspberryPi5),
+            "raspberrypi,500" => model = Some(Model::RaspberryPi500),
+            "raspberrypi,5-compute-module" => model = Some(Model::RaspberryPiComputeModule5),
+            "brcm,bcm2835" => soc = Some(SoC::Bcm2835),
+            "brcm,bcm2836" => soc = Some(SoC::Bcm2836),
+            "brcm,bcm2837" => soc = Some(SoC::Bcm2837),
+            "brcm,bcm2711" => soc = Some(SoC::Bcm2711),
+            "brcm,bcm2712" => soc = Some(SoC::Bcm2712),
+            _ => {}
+        }
+    }
+
+    model.map(|model| (model, soc))
+}
+
+// Returns the model from the device tree's human-readable model property,
+// e.g. "Raspberry Pi 4 Model B Rev 1.4".
+fn parse_dt_model(dt_model: &[u8]) -> Option<Model> {
+    // Longer names first, since several names are prefixes of others.
+    const NAMES: [(&str, Model); 23] = [
+        ("Raspberry Pi Compute Module 5 Lite", Model::RaspberryPiComputeModule5Lite),
+        ("Raspberry Pi Compute Module 5", Model::RaspberryPiComputeModule5),
+        ("Raspberry Pi Compute Module 4S", Model::RaspberryPiComputeModule4S),
+        ("Raspberry Pi Compute Module 4", Model::RaspberryPiComputeModule4),
+        ("Raspberry Pi Compute Module 3 Plus", Model::RaspberryPiComputeModule3Plus),
+        ("Raspberry Pi Compute Module 3", Model::RaspberryPiComputeModule3),
+        ("Raspberry Pi Compute Module", Model::RaspberryPiComputeModule),
+        ("Raspberry Pi 500", Model::RaspberryPi500),
+        ("Raspberry Pi 5", Model::RaspberryPi5),
+        ("Raspberry Pi 400", Model::RaspberryPi400),
+        ("Raspberry Pi 4 Model B", Model::RaspberryPi4ModelB),
+        ("Raspberry Pi 3 Model B Plus", Model::RaspberryPi3ModelBPlus),
+        ("Raspberry Pi 3 Model A Plus", Model::RaspberryPi3ModelAPlus),
+        ("Raspberry Pi 3 Model B", Model::RaspberryPi3ModelB),
+        ("Raspberry Pi 2 Model B", Model::RaspberryPi2ModelB),
+        ("Raspberry Pi Zero 2 W", Model::RaspberryPiZero2W),
+        ("Raspberry Pi Zero W", Model::RaspberryPiZeroW),
+        ("Raspberry Pi Zero", Model::RaspberryPiZero),
+        ("Raspberry Pi Model B Plus", Model::RaspberryPiModelBPlus),
+        ("Raspberry Pi Model A Plus", Model::RaspberryPiModelAPlus),
+        ("Raspberry Pi Model B Rev 2", Model::RaspberryPiModelBRev2),
+        ("Raspberry Pi Model B", Model::RaspberryPiModelBRev1),
+        ("Raspberry Pi Model A", Model::RaspberryPiModelA),
+    ];
 
+    let name = dt_strings(dt_model).next()?;
+    NAMES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|&(_, model)| model)
+}
+
+// Returns the peripheral base address from the device tree's soc/ranges property.
+//
+// Each entry starts with the peripherals' bus address (0x7e000000), followed
+// by the matching ARM physical address, which is one cell wide on older SoCs
+// and two cells wide on the BCM2711. Same approach as bcm_host_get_peripheral_address().
+fn parse_dt_ranges(ranges: &[u8]) -> Option<u64> {
+    let cell = |index: usize| -> Option<u64> {
+        let bytes = ranges.get(index * 4..index * 4 + 4)?;
+        Some(u64::from(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])))
+    };
+
+    match cell(1)? {
+        0 => cell(2).filter(|&address| address != 0),
+        address => Some(address),
     }
+}
 
+impl Model {
+    // The SoC a model ships with, for when it can't be read from the revision code.
+    fn soc(self) -> SoC {
+        match self {
+            Model::RaspberryPiModelA
+            | Model::RaspberryPiModelAPlus
+            | Model::RaspberryPiModelBRev1
+            | Model::RaspberryPiModelBRev2
+            | Model::RaspberryPiModelBPlus
+            | Model::RaspberryPiComputeModule
+            | Model::RaspberryPiZero
+            | Model::RaspberryPiZeroW => SoC::Bcm2835,
+            Model::RaspberryPi2ModelB => SoC::Bcm2836,
+            Model::RaspberryPi3ModelB
+            | Model::RaspberryPi3ModelBPlus
+            | Model::RaspberryPi3ModelAPlus
+            | Model::RaspberryPiComputeModule3
+            | Model::RaspberryPiComputeModule3Plus
+            | Model::RaspberryPiZero2W => SoC::Bcm2837,
+            Model::RaspberryPi4ModelB
+            | Model::RaspberryPi400
+            | Model::RaspberryPiComputeModule4
+            | Model::RaspberryPiComputeModule4S => SoC::Bcm2711,
+            Model::RaspberryPi5
+            | Model::RaspberryPi500
+            | Model::RaspberryPiComputeModule5
+            | Model::RaspberryPiComputeModule5Lite => SoC::Bcm2712,
+        }
+    }
+}
+
+impl DeviceInfo {
+    /// Identifies the Raspberry Pi.
+    ///
+    /// The model and SoC are read from the revision code in `/proc/cpuinfo`. If
+    /// that isn't available, as on some 64-bit and mainline kernels, the
+    /// device tree's `compatible` and `model` properties are tried in turn.
+    /// The peripheral base address is read from `/proc/device-tree/soc/ranges`
+    /// when possible, and otherwise derived from the SoC.
     pub fn new() -> Result<DeviceInfo> {
-        let ret =DeviceInfo::parse_proc_cpuinfo()?;
-        Ok(ret)
+        DeviceInfo::with_root("/")
     }
-}
\ No newline at end of file
+
+    /// Identifies the Raspberry Pi based on the system files found under `root`
+    /// instead of `/`, such as a copy of another board's `/proc` and `/sys`.
+    ///
+    /// Missing or unreadable files are skipped, the same way [`new`] does.
+    ///
+    /// [`new`]: #method.new
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<DeviceInfo> {
+        let path = |absolute: &str| join_root(root.as_ref(), absolute);
+
+        let cpuinfo = fs::read_to_string(path(PATH_PROC_CPUINFO)).ok();
+        let compatible = fs::read(path(PATH_DT_COMPATIBLE)).ok();
+        let dt_model = fs::read(path(PATH_DT_MODEL)).ok();
+        let ranges = fs::read(path(PATH_DT_RANGES)).ok();
+
+        let mut device_info = DeviceInfo::from_contents(
+            cpuinfo.as_deref(),
+            compatible.as_deref(),
+            dt_model.as_deref(),
+            ranges.as_deref(),
+        )?;
+        device_info.root = Some(root.as_ref().to_path_buf());
+
+        Ok(device_info)
+    }
+
+    /// Identifies the Raspberry Pi based on the supplied contents of
+    /// `/proc/cpuinfo`, and the device tree's `compatible`, `model` and
+    /// `soc/ranges` properties. Pass `None` for any file that isn't available.
+    ///
+    /// Returns [`Error::UnknownModel`] if none of them identify a supported model.
+    ///
+    /// [`Error::UnknownModel`]: enum.Error.html#variant.UnknownModel
+    pub fn from_contents(
+        cpuinfo: Option<&str>,
+        compatible: Option<&[u8]>,
+        dt_model: Option<&[u8]>,
+        ranges: Option<&[u8]>,
+    ) -> Result<DeviceInfo> {
+        let mut memory = None;
+        let mut manufacturer = None;
+        let mut board_revision = None;
+
+        let (model, model_source, soc, soc_source) =
+            if let Some(revision) = cpuinfo.and_then(parse_proc_cpuinfo) {
+                memory = Some(revision.memory);
+                manufacturer = Some(revision.manufacturer);
+                board_revision = Some(revision.board_revision);
+                (revision.model, Source::CpuInfo, revision.soc, Source::CpuInfo)
+            } else if let Some((model, soc)) = compatible.and_then(parse_dt_compatible) {
+                // The CM5 Lite shares its compatible string with the CM5, and can
+                // only be told apart by the model property.
+                let (model, model_source) = match dt_model.and_then(parse_dt_model) {
+                    Some(Model::RaspberryPiComputeModule5Lite)
+                        if model == Model::RaspberryPiComputeModule5 =>
+                    {
+                        (Model::RaspberryPiComputeModule5Lite, Source::DeviceTreeModel)
+                    }
+                    _ => (model, Source::DeviceTreeCompatible),
+                };
+
+                match soc {
+                    Some(soc) => (model, model_source, soc, Source::DeviceTreeCompatible),
+                    None => (model, model_source, model.soc(), Source::Default),
+                }
+            } else if let Some(model) = dt_model.and_then(parse_dt_model) {
+                (model, Source::DeviceTreeModel, model.soc(), Source::Default)
+            } else {
+                return Err(Error::UnknownModel);
+            };
+
+        let (default_base, gpio_offset) = match soc {
+            SoC::Bcm2835 => (PERIPHERAL_BASE_RPI, GPIO_OFFSET),
+            SoC::Bcm2836 | SoC::Bcm2837 => (PERIPHERAL_BASE_RPI2, GPIO_OFFSET),
+            SoC::Bcm2711 => (PERIPHERAL_BASE_RPI4, GPIO_OFFSET),
+            SoC::Bcm2712 => (PERIPHERAL_BASE_RP1, GPIO_OFFSET_RP1),
+        };
+
+        // soc/ranges describes the BCM2712's own peripherals, not the RP1.
+        let (peripheral_base, peripheral_base_source) = match ranges.and_then(parse_dt_ranges) {
+            Some(address) if soc != SoC::Bcm2712 => (address, Source::DeviceTreeRanges),
+            _ => (default_base, Source::Default),
+        };
+
+        Ok(DeviceInfo {
+            model,
+            soc,
+            memory,
+            manufacturer,
+            board_revision,
+            peripheral_base,
+            gpio_offset,
+            model_source,
+            soc_source,
+            peripheral_base_source,
+            root: None,
+        })
+    }
+
+    /// Returns the Raspberry Pi's model.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Returns the Raspberry Pi's SoC.
+    pub fn soc(&self) -> SoC {
+        self.soc
+    }
+
+    /// Returns the amount of RAM in megabytes.
+    ///
+    /// Only available when the model was read from `/proc/cpuinfo`.
+    pub fn memory(&self) -> Option<u32> {
+        self.memory
+    }
+
+    /// Returns the board's manufacturer.
+    ///
+    /// Only available when the model was read from `/proc/cpuinfo`.
+    pub fn manufacturer(&self) -> Option<Manufacturer> {
+        self.manufacturer
+    }
+
+    /// Returns the board revision encoded in the lowest 4 bits of a new-style
+    /// revision code, or `0` for the original Pi 1 boards.
+    ///
+    /// Only available when the model was read from `/proc/cpuinfo`.
+    pub fn board_revision(&self) -> Option<u8> {
+        self.board_revision
+    }
+
+    /// Returns the physical address of the SoC's peripherals, as seen by the ARM cores.
+    ///
+    /// On the BCM2712 (Pi 5) this is the base address of the RP1 I/O controller,
+    /// which drives the GPIO pins.
+    pub fn peripheral_base(&self) -> u64 {
+        self.peripheral_base
+    }
+
+    /// Returns the GPIO peripheral's offset from [`peripheral_base`].
+    ///
+    /// [`peripheral_base`]: #method.peripheral_base
+    pub fn gpio_offset(&self) -> u64 {
+        self.gpio_offset
+    }
+
+    /// Returns where the model was read from.
+    pub fn model_source(&self) -> Source {
+        self.model_source
+    }
+
+    /// Returns where the SoC was read from.
+    pub fn soc_source(&self) -> Source {
+        self.soc_source
+    }
+
+    /// Returns where the peripheral base address was read from.
+    pub fn peripheral_base_source(&self) -> Source {
+        self.peripheral_base_source
+    }
+
+    /// Returns the path of the file `source` was read from.
+    ///
+    /// Returns `None` for [`Source::Default`], and if the `DeviceInfo` was
+    /// created by [`from_contents`], which doesn't read any files.
+    ///
+    /// [`Source::Default`]: enum.Source.html#variant.Default
+    /// [`from_contents`]: #method.from_contents
+    pub fn source_path(&self, source: Source) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+
+        source.path().map(|path| join_root(root, path))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_new_style_revision_codes() {
+        let pi4 = Revision::decode(0xd03114).unwrap();
+        assert_eq!(pi4.model, Model::RaspberryPi4ModelB);
+        assert_eq!(pi4.soc, SoC::Bcm2711);
+        assert_eq!(pi4.memory, 8192);
+        assert_eq!(pi4.manufacturer, Manufacturer::SonyUk);
+        assert_eq!(pi4.board_revision, 4);
+
+        let zero2 = Revision::decode(0x902120).unwrap();
+        assert_eq!(zero2.model, Model::RaspberryPiZero2W);
+        assert_eq!(zero2.soc, SoC::Bcm2837);
+        assert_eq!(zero2.memory, 512);
+
+        let cm4 = Revision::decode(0xb03141).unwrap();
+        assert_eq!(cm4.model, Model::RaspberryPiComputeModule4);
+        assert_eq!(cm4.memory, 2048);
+
+        let pi5 = Revision::decode(0xc04170).unwrap();
+        assert_eq!(pi5.model, Model::RaspberryPi5);
+        assert_eq!(pi5.soc, SoC::Bcm2712);
+        assert_eq!(pi5.memory, 4096);
+    }
+
+    #[test]
+    fn decode_old_style_revision_codes() {
+        let b_plus = Revision::decode(0x0010).unwrap();
+        assert_eq!(b_plus.model, Model::RaspberryPiModelBPlus);
+        assert_eq!(b_plus.soc, SoC::Bcm2835);
+        assert_eq!(b_plus.memory, 512);
+
+        // Overvolted boards have bit 24 set.
+        let overvolted = Revision::decode(0x100_0002).unwrap();
+        assert_eq!(overvolted.model, Model::RaspberryPiModelBRev1);
+        assert_eq!(overvolted.manufacturer, Manufacturer::Egoman);
+    }
+
+    #[test]
+    fn decode_rejects_unknown_codes() {
+        assert!(Revision::decode(0x0001).is_err());
+        assert!(Revision::decode(0x9000f0).is_err());
+        assert!(Revision::decode(0xa05082).is_err());
+    }
+
+    #[test]
+    fn detect_falls_back_to_device_tree() {
+        let cpuinfo = "processor\t: 0\nBogoMIPS\t: 108.00\n";
+        let compatible = b"raspberrypi,4-model-b\0brcm,bcm2711\0";
+        let dt_model = b"Raspberry Pi 3 Model B Plus Rev 1.3\0";
+        let ranges = [
+            0x7e, 0, 0, 0, 0, 0, 0, 0, 0xfe, 0, 0, 0, 0x01, 0x80, 0, 0,
+        ];
+
+        let info =
+            DeviceInfo::from_contents(Some(cpuinfo), Some(compatible), Some(dt_model), Some(&ranges))
+                .unwrap();
+        assert_eq!(info.model(), Model::RaspberryPi4ModelB);
+        assert_eq!(info.model_source(), Source::DeviceTreeCompatible);
+        assert_eq!(info.soc(), SoC::Bcm2711);
+        assert_eq!(info.peripheral_base(), 0xfe00_0000);
+        assert_eq!(info.peripheral_base_source(), Source::DeviceTreeRanges);
+        assert_eq!(info.memory(), None);
+
+        let info = DeviceInfo::from_contents(None, None, Some(dt_model), None).unwrap();
+        assert_eq!(info.model(), Model::RaspberryPi3ModelBPlus);
+        assert_eq!(info.model_source(), Source::DeviceTreeModel);
+        assert_eq!(info.soc(), SoC::Bcm2837);
+        assert_eq!(info.soc_source(), Source::Default);
+        assert_eq!(info.peripheral_base(), 0x3f00_0000);
+        assert_eq!(info.peripheral_base_source(), Source::Default);
+
+        assert!(DeviceInfo::from_contents(Some(cpuinfo), None, None, None).is_err());
+    }
+
+    // Boards with a copy of their system files under tests/fixtures/system, and
+    // the source DeviceInfo::with_root identifies their model from.
+    const FIXTURE_BOARDS: [(&str, Model, SoC, u64, Source); 26] = [
+        ("pi1-model-a", Model::RaspberryPiModelA, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi1-model-a-plus", Model::RaspberryPiModelAPlus, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi1-model-b-rev1", Model::RaspberryPiModelBRev1, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi1-model-b-rev2", Model::RaspberryPiModelBRev2, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi1-model-b-plus", Model::RaspberryPiModelBPlus, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("cm1", Model::RaspberryPiComputeModule, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi-zero", Model::RaspberryPiZero, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi-zero-w", Model::RaspberryPiZeroW, SoC::Bcm2835, 0x2000_0000, Source::CpuInfo),
+        ("pi2-model-b", Model::RaspberryPi2ModelB, SoC::Bcm2836, 0x3f00_0000, Source::CpuInfo),
+        ("pi3-model-b", Model::RaspberryPi3ModelB, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("pi3-model-b-plus", Model::RaspberryPi3ModelBPlus, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("pi3-model-a-plus", Model::RaspberryPi3ModelAPlus, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("cm3", Model::RaspberryPiComputeModule3, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("cm3-plus", Model::RaspberryPiComputeModule3Plus, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("pi-zero-2-w", Model::RaspberryPiZero2W, SoC::Bcm2837, 0x3f00_0000, Source::CpuInfo),
+        ("pi4-model-b-8gb", Model::RaspberryPi4ModelB, SoC::Bcm2711, 0xfe00_0000, Source::CpuInfo),
+        ("pi4-model-b-4gb-64bit", Model::RaspberryPi4ModelB, SoC::Bcm2711, 0xfe00_0000, Source::CpuInfo),
+        ("pi400", Model::RaspberryPi400, SoC::Bcm2711, 0xfe00_0000, Source::CpuInfo),
+        ("cm4", Model::RaspberryPiComputeModule4, SoC::Bcm2711, 0xfe00_0000, Source::CpuInfo),
+        ("cm4s", Model::RaspberryPiComputeModule4S, SoC::Bcm2711, 0xfe00_0000, Source::CpuInfo),
+        ("pi5", Model::RaspberryPi5, SoC::Bcm2712, 0x1f_0000_0000, Source::CpuInfo),
+        ("pi500", Model::RaspberryPi500, SoC::Bcm2712, 0x1f_0000_0000, Source::CpuInfo),
+        ("cm5", Model::RaspberryPiComputeModule5, SoC::Bcm2712, 0x1f_0000_0000, Source::CpuInfo),
+        ("cm5-lite", Model::RaspberryPiComputeModule5Lite, SoC::Bcm2712, 0x1f_0000_0000, Source::CpuInfo),
+        ("pi4-model-b-mainline", Model::RaspberryPi4ModelB, SoC::Bcm2711, 0xfe00_0000, Source::DeviceTreeCompatible),
+        ("pi3-model-b-plus-model-only", Model::RaspberryPi3ModelBPlus, SoC::Bcm2837, 0x3f00_0000, Source::DeviceTreeModel),
+    ];
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/system")
+    }
+
+    #[test]
+    fn detect_fixture_boards() {
+        let boards = fs::read_dir(fixtures()).unwrap().filter(|entry| {
+            entry.as_ref().unwrap().file_type().unwrap().is_dir()
+        });
+        assert_eq!(boards.count(), FIXTURE_BOARDS.len());
+
+        for &(board, model, soc, peripheral_base, model_source) in FIXTURE_BOARDS.iter() {
+            let info = DeviceInfo::with_root(fixtures().join(board)).unwrap();
+            assert_eq!(info.model(), model, "{}", board);
+            assert_eq!(info.soc(), soc, "{}", board);
+            assert_eq!(info.peripheral_base(), peripheral_base, "{}", board);
+            assert_eq!(info.model_source(), model_source, "{}", board);
+            assert_eq!(info.memory().is_some(), model_source == Source::CpuInfo, "{}", board);
+        }
+    }
+
+    #[test]
+    fn detect_fixture_boards_without_revision() {
+        for &(board, model, soc, peripheral_base, _) in FIXTURE_BOARDS.iter() {
+            let root = fixtures().join(board);
+            let read = |path: &str| fs::read(join_root(&root, path)).ok();
+
+            // Drop the Revision line, as some 64-bit and mainline kernels do.
+            let cpuinfo = fs::read_to_string(join_root(&root, PATH_PROC_CPUINFO))
+                .unwrap_or_default()
+                .lines()
+                .filter(|line| !line.starts_with("Revision"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let compatible = read(PATH_DT_COMPATIBLE);
+            let dt_model = read(PATH_DT_MODEL);
+            let ranges = read(PATH_DT_RANGES);
+            assert!(dt_model.is_some(), "{}", board);
+
+            let ranges_source = if ranges.is_none() || soc == SoC::Bcm2712 {
+                Source::Default
+            } else {
+                Source::DeviceTreeRanges
+            };
+
+            let info = DeviceInfo::from_contents(
+                Some(&cpuinfo),
+                compatible.as_deref(),
+                dt_model.as_deref(),
+                ranges.as_deref(),
+            )
+            .unwrap();
+            let (model_source, soc_source) = match compatible {
+                Some(_) if model == Model::RaspberryPiComputeModule5Lite => {
+                    (Source::DeviceTreeModel, Source::DeviceTreeCompatible)
+                }
+                Some(_) => (Source::DeviceTreeCompatible, Source::DeviceTreeCompatible),
+                None => (Source::DeviceTreeModel, Source::Default),
+            };
+            assert_eq!(info.model(), model, "{}", board);
+            assert_eq!(info.soc(), soc, "{}", board);
+            assert_eq!(info.peripheral_base(), peripheral_base, "{}", board);
+            assert_eq!(info.model_source(), model_source, "{}", board);
+            assert_eq!(info.soc_source(), soc_source, "{}", board);
+            assert_eq!(info.peripheral_base_source(), ranges_source, "{}", board);
+            assert_eq!(info.memory(), None, "{}", board);
+
+            // The model property on its own.
+            let info =
+                DeviceInfo::from_contents(None, None, dt_model.as_deref(), ranges.as_deref())
+                    .unwrap();
+            assert_eq!(info.model(), model, "{}", board);
+            assert_eq!(info.soc(), soc, "{}", board);
+            assert_eq!(info.peripheral_base(), peripheral_base, "{}", board);
+            assert_eq!(info.model_source(), Source::DeviceTreeModel, "{}", board);
+            assert_eq!(info.soc_source(), Source::Default, "{}", board);
+            assert_eq!(info.peripheral_base_source(), ranges_source, "{}", board);
+        }
+    }
+
+    #[test]
+    fn source_paths_follow_the_root() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/system/pi4-model-b-mainline");
+        let compatible = root.join("sys/firmware/devicetree/base/compatible");
+
+        let info = DeviceInfo::with_root(&root).unwrap();
+        assert_eq!(info.source_path(Source::DeviceTreeCompatible), Some(compatible.clone()));
+        assert_eq!(info.source_path(Source::Default), None);
+        assert!(info.to_string().contains(&compatible.display().to_string()));
+        assert!(!info.to_string().contains("[/sys/"));
+
+        let contents = fs::read(&compatible).unwrap();
+        let info = DeviceInfo::from_contents(None, Some(&contents), None, None).unwrap();
+        assert_eq!(info.source_path(Source::DeviceTreeCompatible), None);
+        assert!(info.to_string().contains(&format!("[{}]", PATH_DT_COMPATIBLE)));
+    }
+
+    #[test]
+    fn detect_without_system_files() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/system/missing");
+
+        assert!(matches!(DeviceInfo::with_root(root), Err(Error::UnknownModel)));
+    }
+}